@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 use tokio::net::TcpListener;
 use tokio::signal;
 use tracing::{error, info};
@@ -25,10 +25,19 @@ async fn main() -> Result<()> {
 
     let app = Router::new().get("/", index);
 
-    let server = serve(listener, app).signal(shutdown_signal());
+    let server = serve(listener, app)
+        .signal(shutdown_signal())
+        .shutdown_timeout(Duration::from_secs(30))
+        .on_shutdown(|| info!("draining connections"))
+        .on_shutdown_complete(|| info!("connections closed"));
 
-    if let Err(e) = server.await {
-        error!("{e}");
+    match server.await {
+        Ok(summary) => info!(
+            "{} connections drained, {} force-closed",
+            summary.drained,
+            summary.forced.len()
+        ),
+        Err(e) => error!("{e}"),
     }
 
     Ok(())
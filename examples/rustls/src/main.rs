@@ -1,6 +1,6 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
-use viz::{Request, Result, Router, get, serve, tls};
+use viz::{Request, Result, Router, get, middleware::hsts, serve, tls};
 
 async fn index(_: Request) -> Result<&'static str> {
     Ok("Hello, World!")
@@ -12,7 +12,19 @@ async fn main() -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
     println!("listening on http://{addr}");
 
-    let app = Router::new().route("/", get(index));
+    let app = Router::new()
+        .route("/", get(index))
+        .with(hsts::Config::new());
+
+    // Redirects the plain HTTP requests to HTTPS.
+    let redirect = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 3080))).await?;
+    tokio::spawn(
+        serve(
+            redirect,
+            tls::HttpsRedirect::new().map_port(3080, 3000).into(),
+        )
+        .into_future(),
+    );
 
     let listener = tls::TlsListener::<_, tls::rustls::TlsAcceptor>::new(
         listener,
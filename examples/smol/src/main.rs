@@ -15,7 +15,9 @@ async fn main(ex: &Arc<smol_macros::Executor<'_>>) -> io::Result<()> {
     println!("listening on {}", listener.local_addr().unwrap());
 
     // Run it
-    viz_smol::serve(ex.clone(), listener, app).await
+    viz_smol::serve(ex.clone(), listener, app).await?;
+
+    Ok(())
 }
 
 async fn handler(_: Request) -> Result<Response> {
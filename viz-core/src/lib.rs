@@ -22,6 +22,13 @@ pub use crate::handler::{ BoxHandler, FnExt, Handler, HandlerExt, IntoHandler, N
 pub mod middleware;
 pub mod types;
 
+#[doc(hidden)]
+pub mod timeout;
+
+#[cfg(feature = "rustls")]
+#[doc(hidden)]
+pub mod tls;
+
 mod body;
 pub use body::{ Body, BodyState };
 
@@ -6,6 +6,8 @@ pub mod cookie;
 pub mod cors;
 #[cfg(feature = "csrf")]
 pub mod csrf;
+#[cfg(feature = "hsts")]
+pub mod hsts;
 #[cfg(feature = "limits")]
 pub mod limits;
 #[cfg(feature = "session")]
@@ -0,0 +1,117 @@
+//! HSTS Middleware.
+//!
+//! Sets the [`Strict-Transport-Security`] header, which tells the browsers to only access the
+//! site using HTTPS.
+//!
+//! The header is only sent over TLS, i.e. the requests with the [`TlsInfo`], the browsers
+//! ignore it over plain HTTP as [RFC 6797] requires.
+//!
+//! [`TlsInfo`]: crate::types::TlsInfo
+//! [RFC 6797]: https://www.rfc-editor.org/rfc/rfc6797#section-7.2
+//! [`Strict-Transport-Security`]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Strict-Transport-Security
+
+use std::time::Duration;
+
+use crate::{
+    Handler, IntoResponse, Request, RequestExt, Response, Result, Transform,
+    header::{HeaderValue, STRICT_TRANSPORT_SECURITY},
+};
+
+/// A configuration for [`HstsMiddleware`].
+#[derive(Clone, Debug)]
+pub struct Config {
+    max_age: Duration,
+    include_sub_domains: bool,
+    preload: bool,
+}
+
+impl Config {
+    /// Creates a new [`Config`], the `max-age` defaults to 1 year.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The time that the browsers should remember that the site is only to be accessed using
+    /// HTTPS.
+    #[must_use]
+    pub const fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Whether the rule applies to all of the subdomains as well.
+    #[must_use]
+    pub const fn include_sub_domains(mut self, include_sub_domains: bool) -> Self {
+        self.include_sub_domains = include_sub_domains;
+        self
+    }
+
+    /// Whether to opt in the [preload list], which requires `max-age` of at least 1 year and
+    /// `includeSubDomains`.
+    ///
+    /// [preload list]: https://hstspreload.org
+    #[must_use]
+    pub const fn preload(mut self, preload: bool) -> Self {
+        self.preload = preload;
+        self
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let mut value = format!("max-age={}", self.max_age.as_secs());
+        if self.include_sub_domains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        HeaderValue::try_from(value).expect("a valid header value")
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(31_536_000),
+            include_sub_domains: false,
+            preload: false,
+        }
+    }
+}
+
+impl<H> Transform<H> for Config {
+    type Output = HstsMiddleware<H>;
+
+    fn transform(&self, h: H) -> Self::Output {
+        HstsMiddleware {
+            h,
+            value: self.header_value(),
+        }
+    }
+}
+
+/// HSTS middleware.
+#[derive(Clone, Debug)]
+pub struct HstsMiddleware<H> {
+    h: H,
+    value: HeaderValue,
+}
+
+#[crate::async_trait]
+impl<H, O> Handler<Request> for HstsMiddleware<H>
+where
+    H: Handler<Request, Output = Result<O>>,
+    O: IntoResponse,
+{
+    type Output = Result<Response>;
+
+    async fn call(&self, req: Request) -> Self::Output {
+        let secure = req.tls_info().is_some();
+        let mut res = self.h.call(req).await.map(IntoResponse::into_response)?;
+        if secure {
+            res.headers_mut()
+                .insert(STRICT_TRANSPORT_SECURITY, self.value.clone());
+        }
+        Ok(res)
+    }
+}
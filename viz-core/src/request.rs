@@ -1,6 +1,6 @@
 use crate::{
     Body, BodyState, Bytes, FromRequest, Future, Request, Result, header,
-    types::{PayloadError, RealIp},
+    types::{ForwardedInfo, PayloadError, RealIp, TlsInfo, TrustedProxies},
 };
 use headers::HeaderMapExt;
 use http_body_util::{BodyExt, Collected};
@@ -32,7 +32,7 @@ use crate::types::{Cookie, Cookies, CookiesError};
 use crate::types::Session;
 
 #[cfg(feature = "params")]
-use crate::types::{ParamsError, PathDeserializer, RouteInfo};
+use crate::types::{ParamsError, PathDeserializer, RouteInfo, UrlFor, UrlForError};
 
 /// The [`Request`] Extension.
 pub trait RequestExt: private::Sealed + Sized {
@@ -174,11 +174,30 @@ pub trait RequestExt: private::Sealed + Sized {
     #[cfg(feature = "params")]
     fn route_info(&self) -> &Arc<RouteInfo>;
 
+    /// Generates the URL of the named route with the params.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`UrlForError`] if the route is not found or a required param is missing.
+    #[cfg(feature = "params")]
+    fn url_for<I, K, V>(&self, name: &str, params: I) -> Result<String, UrlForError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: std::fmt::Display;
+
     /// Get remote addr.
     fn remote_addr(&self) -> Option<&std::net::SocketAddr>;
 
     /// Get realip.
     fn realip(&self) -> Option<RealIp>;
+
+    /// Get the client's information forwarded by the [`TrustedProxies`], which are in the
+    /// extensions of this request.
+    fn forwarded(&self) -> Option<ForwardedInfo>;
+
+    /// Get the TLS information of the connection, e.g. SNI and ALPN.
+    fn tls_info(&self) -> Option<&TlsInfo>;
 }
 
 impl RequestExt for Request {
@@ -373,9 +392,32 @@ impl RequestExt for Request {
         self.extensions().get().expect("should get current route")
     }
 
+    #[cfg(feature = "params")]
+    fn url_for<I, K, V>(&self, name: &str, params: I) -> Result<String, UrlForError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: std::fmt::Display,
+    {
+        self.extensions()
+            .get::<UrlFor>()
+            .ok_or(UrlForError::Unavailable)?
+            .url_for(name, params)
+    }
+
     fn realip(&self) -> Option<RealIp> {
         RealIp::parse(self)
     }
+
+    fn forwarded(&self) -> Option<ForwardedInfo> {
+        self.extensions()
+            .get::<TrustedProxies>()
+            .and_then(|proxies| proxies.resolve(self))
+    }
+
+    fn tls_info(&self) -> Option<&TlsInfo> {
+        self.extensions().get()
+    }
 }
 
 /// The [`Request`] Extension with a limited body.
@@ -0,0 +1,408 @@
+//! The header read, idle and request timeouts of the connections, shared by the servers of
+//! `viz` and `viz-smol` with their own timers.
+
+use std::{
+    fmt, io,
+    pin::{Pin, pin},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll, Waker, ready},
+    time::Duration,
+};
+
+use futures_util::future::{Either, select};
+use hyper::{
+    body::{Frame, SizeHint},
+    rt::{Read, ReadBuf, ReadBufCursor, Sleep, Timer, Write},
+};
+
+use crate::{Body, Bytes, Error, Future, HttpBody, IntoResponse, Response, StatusCode};
+
+/// The timeouts of the connections and the requests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timeouts {
+    /// How long the header of a request can take on HTTP/1 connections, including the wait for
+    /// the next request on a kept-alive connection.
+    pub header_read: Option<Duration>,
+    /// How long a connection can be idle between the requests.
+    pub idle: Option<Duration>,
+    /// How long a handler can take to produce the response.
+    pub request: Option<Duration>,
+}
+
+/// The state of a connection, shared by its IO and its responder.
+pub struct Watch {
+    timeouts: Timeouts,
+    timer: Arc<dyn Timer + Send + Sync>,
+    /// The number of the requests whose responses are not finished yet.
+    requests: AtomicUsize,
+    /// The total number of the requests, tells whether a request was served between reads.
+    served: AtomicUsize,
+    reading: Mutex<Option<Waker>>,
+}
+
+impl Watch {
+    /// Creates the state of a connection with the timer of the runtime.
+    pub fn new<T>(timeouts: Timeouts, timer: T) -> Arc<Self>
+    where
+        T: Timer + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            timeouts,
+            timer: Arc::new(timer),
+            requests: AtomicUsize::new(0),
+            served: AtomicUsize::new(0),
+            reading: Mutex::new(None),
+        })
+    }
+
+    /// Wraps the handling of a request, the request is counted until its response body is
+    /// dropped, and the handler is limited by the request timeout.
+    ///
+    /// `503 Service Unavailable` is responded if the handler times out.
+    pub async fn request<F>(self: Arc<Self>, handler: F) -> Response
+    where
+        F: Future<Output = Response>,
+    {
+        let pending = (self.timeouts.idle.is_some() || self.timeouts.header_read.is_some())
+            .then(|| Pending::new(self.clone()));
+
+        let res = match self.timeouts.request {
+            Some(timeout) => match select(pin!(handler), self.timer.sleep(timeout)).await {
+                Either::Left((res, _)) => res,
+                Either::Right(((), _)) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            },
+            None => handler.await,
+        };
+
+        match pending {
+            Some(pending) => res.map(|body| {
+                Body::wrap(Finishing {
+                    body,
+                    _pending: pending,
+                })
+            }),
+            None => res,
+        }
+    }
+}
+
+impl fmt::Debug for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("timeouts", &self.timeouts)
+            .field("requests", &self.requests)
+            .field("served", &self.served)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counts a request whose response is not finished yet.
+#[derive(Debug)]
+struct Pending(Arc<Watch>);
+
+impl Pending {
+    fn new(watch: Arc<Watch>) -> Self {
+        watch.served.fetch_add(1, Ordering::Relaxed);
+        watch.requests.fetch_add(1, Ordering::Relaxed);
+        Self(watch)
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if self.0.requests.fetch_sub(1, Ordering::Relaxed) == 1
+            && let Some(waker) = self
+                .0
+                .reading
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+        {
+            waker.wake();
+        }
+    }
+}
+
+/// Holds the pending request until the response body is dropped.
+struct Finishing {
+    body: Body,
+    _pending: Pending,
+}
+
+impl HttpBody for Finishing {
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+/// The preface of the HTTP/2 connections.
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Responded once the header of a request times out.
+const REQUEST_TIMEOUT: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+/// Which timeout of the connection expired.
+enum Expired {
+    Header,
+    Idle,
+}
+
+/// The header timer of the HTTP/1 connections.
+#[derive(Default)]
+struct Head {
+    /// Whether the connection is HTTP/2, known once the preface is read or ruled out.
+    http2: Option<bool>,
+    /// How many bytes of the preface are read.
+    preface: usize,
+    /// Whether a part of the header is read.
+    partial: bool,
+    sleep: Option<Pin<Box<dyn Sleep>>>,
+    /// How many bytes of the `408 Request Timeout` response are written.
+    rejected: Option<usize>,
+}
+
+/// Closes the connection if it is idle for too long, i.e. nothing is read and no responses
+/// are pending, or if the header of a request is not read in time.
+///
+/// `408 Request Timeout` is responded if a part of the header is read, otherwise the connection
+/// is closed.
+pub struct Watched<I> {
+    io: I,
+    watch: Arc<Watch>,
+    served: usize,
+    sleep: Option<Pin<Box<dyn Sleep>>>,
+    head: Head,
+}
+
+impl<I> Watched<I> {
+    /// Watches the IO of a connection.
+    pub fn new(io: I, watch: Arc<Watch>) -> Self {
+        Self {
+            io,
+            watch,
+            served: 0,
+            sleep: None,
+            head: Head::default(),
+        }
+    }
+
+    fn header_read(&self) -> Option<Duration> {
+        self.watch
+            .timeouts
+            .header_read
+            .filter(|_| self.head.http2 != Some(true))
+    }
+
+    /// Restarts the timers once a request is served.
+    fn sync_served(&mut self) {
+        let served = self.watch.served.load(Ordering::Relaxed);
+        if served != self.served {
+            self.served = served;
+            self.sleep = None;
+            self.head.sleep = None;
+            self.head.partial = false;
+        }
+    }
+
+    /// Anything read restarts the idle timer, and is a part of the next header if no requests
+    /// are pending.
+    fn on_read(&mut self) {
+        self.sleep = None;
+        if self.watch.requests.load(Ordering::Relaxed) == 0 {
+            self.sync_served();
+            self.head.partial = true;
+        }
+    }
+
+    fn expired(&mut self, cx: &mut Context<'_>) -> Option<Expired> {
+        let idle = self.watch.timeouts.idle;
+        let header_read = self.header_read();
+        if idle.is_none() && header_read.is_none() {
+            return None;
+        }
+
+        // Wakes the reading again once the requests are finished, then the timers start.
+        *self
+            .watch
+            .reading
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+
+        if self.watch.requests.load(Ordering::Relaxed) > 0 {
+            self.sleep = None;
+            self.head.sleep = None;
+            return None;
+        }
+
+        self.sync_served();
+
+        if let Some(timeout) = header_read
+            && self
+                .head
+                .sleep
+                .get_or_insert_with(|| self.watch.timer.sleep(timeout))
+                .as_mut()
+                .poll(cx)
+                .is_ready()
+        {
+            return Some(Expired::Header);
+        }
+
+        if let Some(idle) = idle
+            && self
+                .sleep
+                .get_or_insert_with(|| self.watch.timer.sleep(idle))
+                .as_mut()
+                .poll(cx)
+                .is_ready()
+        {
+            return Some(Expired::Idle);
+        }
+
+        None
+    }
+
+    /// Reads the first bytes by the preface, to tell the HTTP/2 connections.
+    fn poll_read_preface(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>>
+    where
+        I: Read + Unpin,
+    {
+        let mut preface = [0; PREFACE.len()];
+        let len = buf.remaining().min(PREFACE.len() - self.head.preface);
+        let mut read = ReadBuf::new(&mut preface[..len]);
+        let poll = Pin::new(&mut self.io).poll_read(cx, read.unfilled());
+
+        let read = read.filled();
+        if !read.is_empty() {
+            let start = self.head.preface;
+            if PREFACE[start..].starts_with(read) {
+                self.head.preface += read.len();
+                if self.head.preface == PREFACE.len() {
+                    self.head.http2 = Some(true);
+                }
+            } else {
+                self.head.http2 = Some(false);
+            }
+            buf.put_slice(read);
+        }
+
+        poll
+    }
+
+    /// Writes the `408 Request Timeout` response, then closes the connection as if the peer
+    /// had closed it.
+    fn poll_reject(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        I: Write + Unpin,
+    {
+        let written = self.head.rejected.get_or_insert(0);
+        while *written < REQUEST_TIMEOUT.len() {
+            match ready!(Pin::new(&mut self.io).poll_write(cx, &REQUEST_TIMEOUT[*written..])) {
+                Ok(0) | Err(_) => return Poll::Ready(Ok(())),
+                Ok(n) => *written += n,
+            }
+        }
+        let _ = ready!(Pin::new(&mut self.io).poll_flush(cx));
+        Poll::Ready(Ok(()))
+    }
+}
+impl<I> fmt::Debug for Watched<I>
+where
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watched")
+            .field("io", &self.io)
+            .field("watch", &self.watch)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Read for Watched<I>
+where
+    I: Read + Write + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.head.rejected.is_some() {
+            return this.poll_reject(cx);
+        }
+
+        let poll = if this.head.http2.is_none() && this.header_read().is_some() {
+            this.poll_read_preface(cx, buf)
+        } else {
+            Pin::new(&mut this.io).poll_read(cx, buf)
+        };
+        if poll.is_ready() {
+            this.on_read();
+            return poll;
+        }
+
+        match this.expired(cx) {
+            Some(Expired::Header) if this.head.partial => this.poll_reject(cx),
+            // Closes the connection as if the peer had closed it.
+            Some(_) => Poll::Ready(Ok(())),
+            None => poll,
+        }
+    }
+}
+
+impl<I> Write for Watched<I>
+where
+    I: Write + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
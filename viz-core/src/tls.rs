@@ -0,0 +1,4 @@
+//! The TLS configs shared by the servers of `viz` and `viz-smol`.
+
+#[cfg(feature = "rustls")]
+pub mod rustls;
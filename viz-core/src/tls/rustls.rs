@@ -0,0 +1,276 @@
+//! The `rustls` config shared by the servers of `viz` and `viz-smol`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    sync::Arc,
+};
+
+use rustls::{
+    RootCertStore, ServerConfig, ServerConnection,
+    crypto::{CryptoProvider, aws_lc_rs},
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+};
+
+use crate::{Bytes, Error, Result, types::TlsInfo};
+
+/// Tls client authentication configuration.
+#[derive(Clone, Debug)]
+enum ClientAuth {
+    /// No client auth.
+    Off,
+    /// Allow any anonymous or authenticated client.
+    Optional(Vec<u8>),
+    /// Allow any authenticated client.
+    Required(Vec<u8>),
+}
+
+/// `rustls`'s config.
+#[derive(Clone, Debug)]
+pub struct Config {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    ocsp_resp: Vec<u8>,
+    client_auth: ClientAuth,
+    sni: BTreeMap<String, (Vec<u8>, Vec<u8>)>,
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Config {
+    /// Create a new Tls config
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cert: Vec::new(),
+            key: Vec::new(),
+            client_auth: ClientAuth::Off,
+            ocsp_resp: Vec::new(),
+            sni: BTreeMap::new(),
+            alpn_protocols: Vec::new(),
+        }
+    }
+
+    /// sets the Tls certificate
+    #[must_use]
+    pub fn cert(mut self, cert: impl Into<Vec<u8>>) -> Self {
+        self.cert = cert.into();
+        self
+    }
+
+    /// sets the Tls key
+    #[must_use]
+    pub fn key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Sets the trust anchor for optional Tls client authentication
+    #[must_use]
+    pub fn client_auth_optional(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
+        self.client_auth = ClientAuth::Optional(trust_anchor.into());
+        self
+    }
+
+    /// Sets the trust anchor for required Tls client authentication
+    #[must_use]
+    pub fn client_auth_required(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
+        self.client_auth = ClientAuth::Required(trust_anchor.into());
+        self
+    }
+
+    /// sets the DER-encoded OCSP response
+    #[must_use]
+    pub fn ocsp_resp(mut self, ocsp_resp: impl Into<Vec<u8>>) -> Self {
+        self.ocsp_resp = ocsp_resp.into();
+        self
+    }
+
+    /// Sets the Tls certificate and key for the server name sent by the client via SNI, e.g.
+    /// `example.com` or `*.example.com`.
+    ///
+    /// The `cert` and `key` of the config are the default for the other server names, the
+    /// handshakes are rejected if they are not set.
+    #[must_use]
+    pub fn sni(
+        mut self,
+        server_name: impl AsRef<str>,
+        cert: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.sni.insert(
+            server_name.as_ref().to_ascii_lowercase(),
+            (cert.into(), key.into()),
+        );
+        self
+    }
+
+    /// Sets the protocols offered via ALPN in order of preference, e.g. `h2` and `http/1.1`.
+    #[must_use]
+    pub fn alpn_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.alpn_protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// builds the Tls `ServerConfig`
+    ///
+    /// # Errors
+    pub fn build(self) -> Result<ServerConfig> {
+        fn read_trust_anchor(mut trust_anchor: &[u8]) -> Result<RootCertStore> {
+            let certs = rustls_pemfile::certs(&mut trust_anchor)
+                .collect::<IoResult<Vec<_>>>()
+                .map_err(Error::boxed)?;
+            let mut store = RootCertStore::empty();
+            for cert in certs {
+                store.add(cert).map_err(Error::boxed)?;
+            }
+            Ok(store)
+        }
+
+        // Prefers the process-level provider, it can't be inferred when both `ring` and
+        // `aws-lc-rs` are enabled.
+        let provider = CryptoProvider::get_default()
+            .cloned()
+            .unwrap_or_else(|| Arc::new(aws_lc_rs::default_provider()));
+
+        let client_auth = match self.client_auth {
+            ClientAuth::Off => WebPkiClientVerifier::no_client_auth(),
+            ClientAuth::Optional(trust_anchor) => WebPkiClientVerifier::builder_with_provider(
+                read_trust_anchor(&trust_anchor)?.into(),
+                provider.clone(),
+            )
+            .allow_unauthenticated()
+            .build()
+            .map_err(Error::boxed)?,
+            ClientAuth::Required(trust_anchor) => WebPkiClientVerifier::builder_with_provider(
+                read_trust_anchor(&trust_anchor)?.into(),
+                provider.clone(),
+            )
+            .build()
+            .map_err(Error::boxed)?,
+        };
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(Error::boxed)?
+            .with_client_cert_verifier(client_auth);
+
+        let mut config = if self.sni.is_empty() {
+            builder
+                .with_single_cert_with_ocsp(
+                    read_certs(&self.cert)?,
+                    read_key(&self.key)?,
+                    self.ocsp_resp,
+                )
+                .map_err(Error::boxed)?
+        } else {
+            let certified_key = |cert: &[u8], key: &[u8]| -> Result<CertifiedKey> {
+                let key = provider
+                    .key_provider
+                    .load_private_key(read_key(key)?)
+                    .map_err(Error::boxed)?;
+                let certified_key = CertifiedKey::new(read_certs(cert)?, key);
+                certified_key.keys_match().map_err(Error::boxed)?;
+                Ok(certified_key)
+            };
+
+            let default = if self.cert.is_empty() {
+                None
+            } else {
+                let mut default = certified_key(&self.cert, &self.key)?;
+                if !self.ocsp_resp.is_empty() {
+                    default.ocsp = Some(self.ocsp_resp);
+                }
+                Some(Arc::new(default))
+            };
+
+            let names = self
+                .sni
+                .iter()
+                .map(|(name, (cert, key))| Ok((name.clone(), Arc::new(certified_key(cert, key)?))))
+                .collect::<Result<_>>()?;
+
+            builder.with_cert_resolver(Arc::new(SniResolver { names, default }))
+        };
+
+        config.alpn_protocols = self.alpn_protocols;
+
+        Ok(config)
+    }
+}
+
+fn read_certs(mut cert: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+    rustls_pemfile::certs(&mut cert)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::boxed)
+}
+
+fn read_key(key: &[u8]) -> Result<PrivateKeyDer<'static>> {
+    let mut pkcs8 = rustls_pemfile::pkcs8_private_keys(&mut &key[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::boxed)?;
+    if !pkcs8.is_empty() {
+        return Ok(PrivateKeyDer::Pkcs8(pkcs8.remove(0)));
+    }
+
+    let mut rsa = rustls_pemfile::rsa_private_keys(&mut &key[..])
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::boxed)?;
+    if rsa.is_empty() {
+        return Err(Error::boxed(IoError::new(
+            ErrorKind::InvalidData,
+            "failed to parse tls private keys",
+        )));
+    }
+    Ok(PrivateKeyDer::Pkcs1(rsa.remove(0)))
+}
+
+/// Selects the certificate by the server name sent by the client via SNI.
+#[derive(Debug)]
+struct SniResolver {
+    names: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let Some(name) = client_hello.server_name().map(str::to_ascii_lowercase) else {
+            return self.default.clone();
+        };
+
+        self.names
+            .get(&name)
+            .or_else(|| {
+                let (_, parent) = name.split_once('.')?;
+                self.names.get(&format!("*.{parent}"))
+            })
+            .or(self.default.as_ref())
+            .cloned()
+    }
+}
+
+/// Returns the TLS information of the connection.
+#[must_use]
+pub fn tls_info(conn: &ServerConnection) -> TlsInfo {
+    TlsInfo {
+        server_name: conn.server_name().map(ToOwned::to_owned),
+        alpn_protocol: conn.alpn_protocol().map(<[u8]>::to_vec),
+        peer_certificates: conn.peer_certificates().map(|certs| {
+            certs
+                .iter()
+                .map(|cert| Bytes::copy_from_slice(cert))
+                .collect()
+        }),
+    }
+}
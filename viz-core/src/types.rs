@@ -64,6 +64,11 @@ mod route_info;
 #[cfg(feature = "params")]
 pub use route_info::RouteInfo;
 
+#[cfg(feature = "params")]
+mod url_for;
+#[cfg(feature = "params")]
+pub use url_for::{UrlFor, UrlForError};
+
 mod header;
 pub use header::{Header, HeaderError};
 
@@ -71,4 +76,12 @@ mod payload;
 pub use payload::{Payload, PayloadError};
 
 mod realip;
-pub use realip::RealIp;
+pub use realip::{ForwardedInfo, IpNet, RealIp, TrustedProxies};
+
+mod tls;
+pub use tls::{TlsError, TlsInfo};
+
+#[cfg(feature = "client-cert")]
+mod client_cert;
+#[cfg(feature = "client-cert")]
+pub use client_cert::{ClientCert, ClientCertError, SubjectAltName};
@@ -0,0 +1,196 @@
+//! The client certificate of the mutual TLS.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+use x509_cert::{
+    Certificate,
+    attr::AttributeTypeAndValue,
+    der::{
+        self, Decode, Tag, Tagged,
+        asn1::{Ia5StringRef, PrintableStringRef, TeletexStringRef, Utf8StringRef},
+        oid::db::rfc4519::COMMON_NAME,
+    },
+    ext::pkix::{self, name::GeneralName},
+};
+
+use crate::{
+    Bytes, Error, FromRequest, IntoResponse, Request, RequestExt, Response, StatusCode, ThisError,
+};
+
+/// The certificate presented by the client and verified in the mutual TLS.
+///
+/// The server should require or allow the client certificates, e.g. by
+/// `client_auth_required` of the `rustls` config, otherwise it is always missing.
+///
+/// ```
+/// use viz_core::{Result, types::ClientCert};
+///
+/// async fn handler(cert: ClientCert) -> Result<String> {
+///     Ok(format!("hello, {}", cert.common_name().unwrap_or(cert.subject())))
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClientCert {
+    chain: Arc<[Bytes]>,
+    subject: String,
+    common_name: Option<String>,
+    subject_alt_names: Vec<SubjectAltName>,
+}
+
+impl ClientCert {
+    /// Parses the DER-encoded certificate chain, the client's certificate comes first.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`ClientCertError`] if the chain is empty or the client's certificate is
+    /// invalid.
+    pub fn parse(chain: Arc<[Bytes]>) -> Result<Self, ClientCertError> {
+        let cert = chain.first().ok_or(ClientCertError::Missing)?;
+        let (subject, common_name, subject_alt_names) =
+            parse_certificate(cert).map_err(|e| ClientCertError::Invalid(e.to_string()))?;
+
+        Ok(Self {
+            chain,
+            subject,
+            common_name,
+            subject_alt_names,
+        })
+    }
+
+    /// The DER-encoded certificate chain, the client's certificate comes first.
+    #[must_use]
+    pub fn chain(&self) -> &[Bytes] {
+        &self.chain
+    }
+
+    /// The subject of the client's certificate in the [RFC 4514] form, e.g.
+    /// `CN=billing,O=Viz`.
+    ///
+    /// [RFC 4514]: https://www.rfc-editor.org/rfc/rfc4514
+    #[must_use]
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The common name (`CN`) of the subject.
+    #[must_use]
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// The subject alternative names of the client's certificate.
+    #[must_use]
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+}
+
+impl FromRequest for ClientCert {
+    type Error = ClientCertError;
+
+    async fn extract(req: &mut Request) -> Result<Self, Self::Error> {
+        req.tls_info()
+            .and_then(|info| info.peer_certificates.clone())
+            .ok_or(ClientCertError::Missing)
+            .and_then(Self::parse)
+    }
+}
+
+/// A subject alternative name of the certificate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SubjectAltName {
+    /// A DNS name, e.g. `billing.internal`.
+    Dns(String),
+    /// An email address.
+    Email(String),
+    /// An URI, e.g. `spiffe://viz/billing`.
+    Uri(String),
+    /// An IP address.
+    Ip(IpAddr),
+}
+
+/// Rejects an error of the client certificate.
+#[derive(Debug, ThisError)]
+pub enum ClientCertError {
+    /// Represents the client certificate is missing.
+    #[error("missing client certificate")]
+    Missing,
+    /// Represents the client certificate can not be parsed.
+    #[error("invalid client certificate: {}", .0)]
+    Invalid(String),
+}
+
+impl IntoResponse for ClientCertError {
+    fn into_response(self) -> Response {
+        (
+            match self {
+                Self::Missing => StatusCode::UNAUTHORIZED,
+                Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            },
+            self.to_string(),
+        )
+            .into_response()
+    }
+}
+
+impl From<ClientCertError> for Error {
+    fn from(e: ClientCertError) -> Self {
+        e.into_error()
+    }
+}
+
+/// Parses the subject, its common name and the subject alternative names of the certificate.
+fn parse_certificate(cert: &[u8]) -> der::Result<(String, Option<String>, Vec<SubjectAltName>)> {
+    let tbs = Certificate::from_der(cert)?.tbs_certificate;
+
+    // The most specific common name comes last.
+    let common_name = tbs
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .filter(|attribute| attribute.oid == COMMON_NAME)
+        .filter_map(attribute_text)
+        .next_back();
+
+    let subject_alt_names = tbs
+        .get::<pkix::SubjectAltName>()?
+        .map(|(_, names)| names.0.iter().filter_map(general_name).collect())
+        .unwrap_or_default();
+
+    // In the RFC 4514 form, the values of the other types are in the hex form.
+    Ok((tbs.subject.to_string(), common_name, subject_alt_names))
+}
+
+/// Decodes the attribute value of the string types, as the subject is formatted.
+fn attribute_text(attribute: &AttributeTypeAndValue) -> Option<String> {
+    let value = &attribute.value;
+    let text = match value.tag() {
+        Tag::Utf8String => Utf8StringRef::try_from(value).ok()?.as_str(),
+        Tag::PrintableString => PrintableStringRef::try_from(value).ok()?.as_str(),
+        Tag::Ia5String => Ia5StringRef::try_from(value).ok()?.as_str(),
+        // Only the ASCII subset is decoded.
+        Tag::TeletexString => TeletexStringRef::try_from(value).ok()?.as_str(),
+        _ => return None,
+    };
+    Some(text.to_owned())
+}
+
+fn general_name(name: &GeneralName) -> Option<SubjectAltName> {
+    match name {
+        GeneralName::Rfc822Name(email) => Some(SubjectAltName::Email(email.to_string())),
+        GeneralName::DnsName(dns) => Some(SubjectAltName::Dns(dns.to_string())),
+        GeneralName::UniformResourceIdentifier(uri) => Some(SubjectAltName::Uri(uri.to_string())),
+        GeneralName::IpAddress(ip) => match ip.as_bytes() {
+            &[a, b, c, d] => Some(SubjectAltName::Ip(Ipv4Addr::new(a, b, c, d).into())),
+            octets => <[u8; 16]>::try_from(octets)
+                .ok()
+                .map(|octets| SubjectAltName::Ip(Ipv6Addr::from(octets).into())),
+        },
+        _ => None,
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     str,
 };
 
+use http::uri::Scheme;
 use rfc7239::{NodeIdentifier, NodeName};
 
 use crate::{
@@ -10,7 +11,12 @@ use crate::{
     header::{FORWARDED, HeaderValue},
 };
 
+pub use ipnet::IpNet;
+
 /// Gets real ip remote addr from request headers.
+///
+/// The headers are trusted from any client by default, which could be spoofed. Configures
+/// the [`TrustedProxies`] to only honor the headers sent by the proxies.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct RealIp(pub IpAddr);
 
@@ -22,7 +28,13 @@ impl RealIp {
     pub const X_FORWARDED_FOR: &'static str = "x-forwarded-for";
 
     /// Parse the headers.
+    ///
+    /// Resolves by the [`TrustedProxies`] if they are in the extensions of the request.
     pub fn parse(req: &Request) -> Option<Self> {
+        if let Some(proxies) = req.extensions().get::<TrustedProxies>() {
+            return proxies.resolve(req).map(|forwarded| Self(forwarded.ip));
+        }
+
         req.headers()
             .get(Self::X_REAL_IP)
             .map(HeaderValue::to_str)
@@ -65,3 +77,151 @@ impl RealIp {
             .or_else(|| req.remote_addr().map(SocketAddr::ip).map(RealIp))
     }
 }
+
+/// The client's information forwarded by the [`TrustedProxies`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ForwardedInfo {
+    /// The IP address of the client.
+    pub ip: IpAddr,
+    /// The scheme which is used by the client, from the `proto` of `Forwarded`.
+    pub proto: Option<Scheme>,
+    /// The host which is requested by the client, from the `host` of `Forwarded`.
+    pub host: Option<String>,
+}
+
+/// The proxies whose forwarded headers are honored, e.g. the load balancers.
+///
+/// The chain of `Forwarded`, or `X-Forwarded-For` if it is missing, is walked from right to
+/// left, starting at the peer. Each trusted hop exposes the address on its left, the first
+/// untrusted one is the client. `X-Real-IP` is honored only if the peer is trusted and there
+/// is no chain. If the chain is malformed, the peer is the client.
+///
+/// It takes effect once inserted into the extensions of the requests, e.g. by
+/// `Server::trusted_proxies` of `viz`, or by the [`State`](super::State) middleware.
+///
+/// ```
+/// use viz_core::types::{IpNet, TrustedProxies};
+///
+/// let proxies = TrustedProxies::new()
+///     .trust("10.0.0.0/8".parse::<IpNet>().unwrap())
+///     .trust("::1".parse::<std::net::IpAddr>().unwrap());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+    hops: usize,
+}
+
+impl TrustedProxies {
+    /// Creates a new config, no proxy is trusted.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts the proxies in the network, an IP address is a network of itself.
+    #[must_use]
+    pub fn trust<N>(mut self, network: N) -> Self
+    where
+        N: Into<IpNet>,
+    {
+        self.networks.push(network.into());
+        self
+    }
+
+    /// Trusts the nearest hops, whatever their addresses, the peer is the first hop.
+    ///
+    /// It suits the proxies whose addresses are unknown, e.g. a cloud load balancer.
+    #[must_use]
+    pub const fn hops(mut self, hops: usize) -> Self {
+        self.hops = hops;
+        self
+    }
+
+    /// Checks if the `n`th hop, counted from 0 at the peer, is trusted.
+    fn is_trusted(&self, ip: &IpAddr, n: usize) -> bool {
+        n < self.hops || self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// Resolves the client's information of the request.
+    ///
+    /// Returns `None` if the request has no remote address.
+    pub fn resolve(&self, req: &Request) -> Option<ForwardedInfo> {
+        let mut client = ForwardedInfo {
+            ip: req.remote_addr()?.ip().to_canonical(),
+            proto: None,
+            host: None,
+        };
+
+        // A malformed header can not be walked, and is not replaced by the next one, which
+        // could be forged by the client while the proxies only append to the former.
+        let chain = if req.headers().contains_key(FORWARDED) {
+            forwarded_chain(req)
+        } else if req.headers().contains_key(RealIp::X_FORWARDED_FOR) {
+            x_forwarded_for_chain(req)
+        } else {
+            if self.is_trusted(&client.ip, 0) {
+                if let Some(ip) = req.header(RealIp::X_REAL_IP) {
+                    client.ip = ip;
+                }
+            }
+            return Some(client);
+        };
+        let Some(chain) = chain else {
+            return Some(client);
+        };
+
+        for (n, hop) in chain.into_iter().rev().enumerate() {
+            if !self.is_trusted(&client.ip, n) {
+                break;
+            }
+            // An unknown or obfuscated node can not be walked further.
+            let Some(forwarded) = hop else {
+                break;
+            };
+            client = forwarded;
+        }
+
+        Some(client)
+    }
+}
+
+/// Parses the `Forwarded` headers, the nodes without an IP address are `None`.
+///
+/// Returns `None` if any of the headers is malformed or empty.
+fn forwarded_chain(req: &Request) -> Option<Vec<Option<ForwardedInfo>>> {
+    let mut chain = Vec::new();
+    for value in req.headers().get_all(FORWARDED) {
+        for item in rfc7239::parse(value.to_str().ok()?) {
+            let item = item.ok()?;
+            chain.push(
+                item.forwarded_for
+                    .as_ref()
+                    .and_then(NodeIdentifier::ip)
+                    .map(|ip| ForwardedInfo {
+                        ip: ip.to_canonical(),
+                        proto: item.protocol.and_then(|proto| Scheme::try_from(proto).ok()),
+                        host: item.host.map(ToOwned::to_owned),
+                    }),
+            );
+        }
+    }
+    (!chain.is_empty()).then_some(chain)
+}
+
+/// Parses the `X-Forwarded-For` headers, the invalid addresses are `None`.
+///
+/// Returns `None` if any of the headers is not a string or empty.
+fn x_forwarded_for_chain(req: &Request) -> Option<Vec<Option<ForwardedInfo>>> {
+    let mut chain = Vec::new();
+    for value in req.headers().get_all(RealIp::X_FORWARDED_FOR) {
+        for ip in value.to_str().ok()?.split(',') {
+            chain.push(ip.trim().parse::<IpAddr>().ok().map(|ip| ForwardedInfo {
+                ip: ip.to_canonical(),
+                proto: None,
+                host: None,
+            }));
+        }
+    }
+    (!chain.is_empty()).then_some(chain)
+}
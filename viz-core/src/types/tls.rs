@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use crate::{
+    Bytes, Error, FromRequest, IntoResponse, Request, RequestExt, Response, StatusCode, ThisError,
+};
+
+/// The TLS information of the connection, which is negotiated in the handshake.
+///
+/// It is only available on the connections accepted by a TLS listener.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TlsInfo {
+    /// The server name sent by the client via SNI.
+    pub server_name: Option<String>,
+    /// The application protocol negotiated via ALPN, e.g. `h2`.
+    pub alpn_protocol: Option<Vec<u8>>,
+    /// The DER-encoded certificate chain verified in the mutual TLS, the client's certificate
+    /// comes first.
+    pub peer_certificates: Option<Arc<[Bytes]>>,
+}
+
+impl FromRequest for TlsInfo {
+    type Error = TlsError;
+
+    async fn extract(req: &mut Request) -> Result<Self, Self::Error> {
+        req.tls_info().cloned().ok_or(TlsError::Unavailable)
+    }
+}
+
+/// Rejects an error of the TLS information.
+#[derive(Debug, ThisError)]
+pub enum TlsError {
+    /// Represents the connection is not secured by TLS.
+    #[error("the connection is not secured by TLS")]
+    Unavailable,
+}
+
+impl IntoResponse for TlsError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+    }
+}
+
+impl From<TlsError> for Error {
+    fn from(e: TlsError) -> Self {
+        e.into_error()
+    }
+}
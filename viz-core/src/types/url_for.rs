@@ -0,0 +1,186 @@
+//! Generates URLs of the named routes.
+
+use std::{collections::HashMap, fmt::Display, sync::Arc};
+
+use path_tree::{Kind, Parser, Piece, Position};
+use percent_encoding::{AsciiSet, CONTROLS, NON_ALPHANUMERIC, utf8_percent_encode};
+
+use crate::{Error, FromRequest, IntoResponse, Request, Response, Result, StatusCode, ThisError};
+
+/// The characters are percent-encoded in a path segment.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// The characters are percent-encoded in a catch-all path.
+const PATH: &AsciiSet = &SEGMENT.remove(b'/');
+
+/// The characters are percent-encoded in a query string.
+const QUERY: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Generates URLs of the named routes.
+#[derive(Clone, Debug, Default)]
+pub struct UrlFor(Arc<HashMap<String, Vec<Piece>>>);
+
+impl UrlFor {
+    /// Creates a new `UrlFor` by the name-pattern pairs.
+    pub fn new<I, N, P>(routes: I) -> Self
+    where
+        I: IntoIterator<Item = (N, P)>,
+        N: Into<String>,
+        P: AsRef<str>,
+    {
+        Self(Arc::new(
+            routes
+                .into_iter()
+                .map(|(name, pattern)| (name.into(), Parser::new(pattern.as_ref()).collect()))
+                .collect(),
+        ))
+    }
+
+    /// Returns `true` if there is a route with the name.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Generates the URL of the named route with the params.
+    ///
+    /// The params are percent-encoded, the ones not in the pattern are appended as the query
+    /// string.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`UrlForError`] if the route is not found or a required param is missing or
+    /// empty.
+    pub fn url_for<I, K, V>(&self, name: &str, params: I) -> Result<String, UrlForError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: Display,
+    {
+        let pieces = self
+            .0
+            .get(name)
+            .ok_or_else(|| UrlForError::NotFound(name.to_string()))?;
+
+        let mut params = params
+            .into_iter()
+            .map(|(k, v)| (Some(k), v.to_string()))
+            .collect::<Vec<_>>();
+        let mut url = String::new();
+
+        for piece in pieces {
+            let (name, kind) = match piece {
+                Piece::String(s) => {
+                    url.push_str(&String::from_utf8_lossy(s));
+                    continue;
+                }
+                Piece::Parameter(Position::Index(_, name) | Position::Named(name), kind) => {
+                    (String::from_utf8_lossy(name), kind)
+                }
+            };
+
+            let value = params
+                .iter_mut()
+                .find(|(k, _)| k.as_ref().is_some_and(|k| k.as_ref() == name))
+                .map(|(k, v)| {
+                    k.take();
+                    v.as_str()
+                });
+
+            match (value, kind) {
+                (Some(""), Kind::Normal | Kind::OneOrMore) => {
+                    return Err(UrlForError::EmptyParam(name.into_owned()));
+                }
+                (Some(value), Kind::OneOrMore | Kind::ZeroOrMore | Kind::ZeroOrMoreSegment) => {
+                    url.extend(utf8_percent_encode(value, PATH));
+                }
+                (Some(value), _) => {
+                    url.extend(utf8_percent_encode(value, SEGMENT));
+                }
+                (None, Kind::Optional | Kind::ZeroOrMore) => {}
+                (None, Kind::OptionalSegment | Kind::ZeroOrMoreSegment) => {
+                    if url.len() > 1 && url.ends_with('/') {
+                        url.pop();
+                    }
+                }
+                (None, Kind::Normal | Kind::OneOrMore) => {
+                    return Err(UrlForError::MissingParam(name.into_owned()));
+                }
+            }
+        }
+
+        let query = params
+            .iter()
+            .filter_map(|(k, v)| k.as_ref().map(|k| (k, v)))
+            .map(|(k, v)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(k.as_ref(), QUERY),
+                    utf8_percent_encode(v, QUERY)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+
+        Ok(url)
+    }
+}
+
+impl FromRequest for UrlFor {
+    type Error = UrlForError;
+
+    async fn extract(req: &mut Request) -> Result<Self, Self::Error> {
+        req.extensions()
+            .get::<Self>()
+            .cloned()
+            .ok_or(UrlForError::Unavailable)
+    }
+}
+
+/// Rejects an error of generating URLs.
+#[derive(Debug, ThisError)]
+pub enum UrlForError {
+    /// Represents the named routes are unavailable in the request.
+    #[error("named routes are unavailable")]
+    Unavailable,
+    /// Represents the named route is not found.
+    #[error("route `{}` is not found", .0)]
+    NotFound(String),
+    /// Represents a required param is missing.
+    #[error("missing `{}` param", .0)]
+    MissingParam(String),
+    /// Represents a required param is empty.
+    #[error("empty `{}` param", .0)]
+    EmptyParam(String),
+}
+
+impl IntoResponse for UrlForError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
+
+impl From<UrlForError> for Error {
+    fn from(e: UrlForError) -> Self {
+        e.into_error()
+    }
+}
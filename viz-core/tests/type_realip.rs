@@ -1,9 +1,10 @@
 //! `RealIp` type test cases
 
+use http::uri::Scheme;
 use viz_core::{
     Request, RequestExt,
     header::{FORWARDED, HeaderValue},
-    types::RealIp,
+    types::{IpNet, RealIp, TrustedProxies},
 };
 
 #[test]
@@ -33,3 +34,112 @@ fn realip() {
         .insert("1.1.1.1:80".parse::<std::net::SocketAddr>().unwrap());
     assert_eq!(req.realip(), Some(RealIp("1.1.1.1".parse().unwrap())));
 }
+
+fn request(peer: &str, headers: &[(&'static str, &'static str)]) -> Request {
+    let mut req = Request::default();
+    req.extensions_mut()
+        .insert(peer.parse::<std::net::SocketAddr>().unwrap());
+    req.extensions_mut().insert(
+        TrustedProxies::new()
+            .trust("10.0.0.0/8".parse::<IpNet>().unwrap())
+            .trust("::1".parse::<std::net::IpAddr>().unwrap()),
+    );
+    for (name, value) in headers {
+        req.headers_mut()
+            .append(*name, HeaderValue::from_static(value));
+    }
+    req
+}
+
+#[test]
+fn realip_trusted_proxies() {
+    // The headers from an untrusted peer are ignored.
+    let req = request("1.1.1.1:80", &[(RealIp::X_FORWARDED_FOR, "2.2.2.2")]);
+    assert_eq!(req.realip(), Some(RealIp("1.1.1.1".parse().unwrap())));
+    let req = request("1.1.1.1:80", &[(RealIp::X_REAL_IP, "2.2.2.2")]);
+    assert_eq!(req.realip(), Some(RealIp("1.1.1.1".parse().unwrap())));
+
+    // The spoofed entries on the left of the first untrusted hop are ignored.
+    let req = request(
+        "10.0.0.2:80",
+        &[(RealIp::X_FORWARDED_FOR, "6.6.6.6, 3.3.3.3, 10.0.0.1")],
+    );
+    assert_eq!(req.realip(), Some(RealIp("3.3.3.3".parse().unwrap())));
+
+    // The multiple headers are concatenated.
+    let req = request(
+        "10.0.0.2:80",
+        &[
+            (RealIp::X_FORWARDED_FOR, "3.3.3.3"),
+            (RealIp::X_FORWARDED_FOR, "10.0.0.1"),
+        ],
+    );
+    assert_eq!(req.realip(), Some(RealIp("3.3.3.3".parse().unwrap())));
+
+    // All the hops are trusted.
+    let req = request(
+        "[::1]:80",
+        &[(RealIp::X_FORWARDED_FOR, "10.0.0.3, 10.0.0.1")],
+    );
+    assert_eq!(req.realip(), Some(RealIp("10.0.0.3".parse().unwrap())));
+
+    let req = request("10.0.0.2:80", &[(RealIp::X_REAL_IP, "3.3.3.3")]);
+    assert_eq!(req.realip(), Some(RealIp("3.3.3.3".parse().unwrap())));
+
+    // `Forwarded` takes precedence, an IPv4-mapped peer is trusted as IPv4.
+    let req = request(
+        "[::ffff:10.0.0.2]:80",
+        &[
+            (
+                "forwarded",
+                r#"for=6.6.6.6, for="[2001:db8::3]:4711";proto=https;host=viz.rs, for=10.0.0.1;proto=http"#,
+            ),
+            (RealIp::X_FORWARDED_FOR, "4.4.4.4"),
+        ],
+    );
+    assert_eq!(req.realip(), Some(RealIp("2001:db8::3".parse().unwrap())));
+    let forwarded = req.forwarded().unwrap();
+    assert_eq!(forwarded.proto, Some(Scheme::HTTPS));
+    assert_eq!(forwarded.host.as_deref(), Some("viz.rs"));
+
+    // A malformed `Forwarded` is not replaced by the forged `X-Forwarded-For`.
+    let req = request(
+        "10.0.0.2:80",
+        &[
+            ("forwarded", "for=\"garbage"),
+            (RealIp::X_FORWARDED_FOR, "6.6.6.6"),
+            (RealIp::X_REAL_IP, "6.6.6.6"),
+        ],
+    );
+    assert_eq!(req.realip(), Some(RealIp("10.0.0.2".parse().unwrap())));
+    let mut req = request("10.0.0.2:80", &[(RealIp::X_FORWARDED_FOR, "6.6.6.6")]);
+    req.headers_mut()
+        .append("forwarded", HeaderValue::from_bytes(b"for=\xff").unwrap());
+    assert_eq!(req.realip(), Some(RealIp("10.0.0.2".parse().unwrap())));
+
+    // So is a malformed `X-Forwarded-For` by the forged `X-Real-IP`.
+    let mut req = request("10.0.0.2:80", &[(RealIp::X_REAL_IP, "6.6.6.6")]);
+    req.headers_mut().append(
+        RealIp::X_FORWARDED_FOR,
+        HeaderValue::from_bytes(b"\xff").unwrap(),
+    );
+    assert_eq!(req.realip(), Some(RealIp("10.0.0.2".parse().unwrap())));
+
+    // An obfuscated node stops the walk.
+    let req = request("10.0.0.2:80", &[("forwarded", "for=_hidden, for=10.0.0.1")]);
+    assert_eq!(req.realip(), Some(RealIp("10.0.0.1".parse().unwrap())));
+
+    // The nearest hops are trusted by count.
+    let mut req = request(
+        "1.1.1.1:80",
+        &[(RealIp::X_FORWARDED_FOR, "3.3.3.3, 5.5.5.5, 2.2.2.2")],
+    );
+    req.extensions_mut().insert(TrustedProxies::new().hops(2));
+    assert_eq!(req.realip(), Some(RealIp("5.5.5.5".parse().unwrap())));
+    assert_eq!(req.forwarded().unwrap().proto, None);
+
+    // Without the remote address.
+    let mut req = request("1.1.1.1:80", &[]);
+    req.extensions_mut().remove::<std::net::SocketAddr>();
+    assert_eq!(req.realip(), None);
+}
@@ -0,0 +1,75 @@
+//! `UrlFor` type test cases
+
+use viz_core::{
+    Request, RequestExt,
+    types::{UrlFor, UrlForError},
+};
+
+#[test]
+fn url_for() {
+    let urls = UrlFor::new([
+        ("user.show", "/users/:user_id"),
+        ("user.posts", "/users/:user_id/posts/:page?"),
+        ("files", "/files/*"),
+        ("tag", "/tags/:name"),
+    ]);
+
+    assert!(urls.contains("user.show"));
+    assert!(!urls.contains("user.index"));
+
+    assert_eq!(
+        urls.url_for("user.show", [("user_id", 7)]).unwrap(),
+        "/users/7"
+    );
+    assert_eq!(
+        urls.url_for("user.show", [("user_id", "7"), ("tab", "a b&c")])
+            .unwrap(),
+        "/users/7?tab=a%20b%26c"
+    );
+    assert_eq!(
+        urls.url_for("user.posts", [("user_id", 7)]).unwrap(),
+        "/users/7/posts"
+    );
+    assert_eq!(
+        urls.url_for("user.posts", [("user_id", 7), ("page", 2)])
+            .unwrap(),
+        "/users/7/posts/2"
+    );
+    assert_eq!(
+        urls.url_for("files", [("*1", "css/a b.css")]).unwrap(),
+        "/files/css/a%20b.css"
+    );
+    assert_eq!(
+        urls.url_for("tag", [("name", "a/b?")]).unwrap(),
+        "/tags/a%2Fb%3F"
+    );
+
+    assert!(matches!(
+        urls.url_for("user.show", [("id", 7)]),
+        Err(UrlForError::MissingParam(name)) if name == "user_id"
+    ));
+    assert!(matches!(
+        urls.url_for("user.show", [("user_id", "")]),
+        Err(UrlForError::EmptyParam(name)) if name == "user_id"
+    ));
+    assert_eq!(
+        urls.url_for("user.posts", [("user_id", "7"), ("page", "")])
+            .unwrap(),
+        "/users/7/posts/"
+    );
+    assert!(matches!(
+        urls.url_for("user.index", [("id", 7)]),
+        Err(UrlForError::NotFound(name)) if name == "user.index"
+    ));
+
+    let mut req = Request::default();
+    assert!(matches!(
+        req.url_for("user.show", [("user_id", 7)]),
+        Err(UrlForError::Unavailable)
+    ));
+    req.extensions_mut().insert(urls);
+    assert_eq!(
+        req.url_for("user.show", [("user_id", 7)]).unwrap(),
+        "/users/7"
+    );
+}
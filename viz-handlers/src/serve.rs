@@ -110,10 +110,8 @@ impl Handler<Request> for Dir {
         let mut path = self.path.clone();
 
         if let Some(param) = req.route_info().params.first().map(|(_, v)| v) {
-            let p = percent_encoding::percent_decode_str(param)
-                .decode_utf8()
-                .map_err(|_| Error::InvalidPath)?;
-            sanitize_path(&mut path, &p)?;
+            // The router has percent-decoded the param already.
+            sanitize_path(&mut path, param)?;
             prev = true;
         }
 
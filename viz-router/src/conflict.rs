@@ -0,0 +1,55 @@
+//! Route conflicts
+
+use std::fmt;
+
+use viz_core::{Method, ThisError};
+
+/// A conflict between the registered routes, the last registered handler is kept.
+#[derive(Clone, Debug, PartialEq, Eq, ThisError)]
+pub enum Conflict {
+    /// The verb and pattern pair is registered more than once.
+    #[error("duplicate route `{method} {pattern}`")]
+    Duplicate {
+        /// HTTP verb
+        method: Method,
+        /// Route pattern
+        pattern: String,
+    },
+    /// The patterns only differ in the names of the params, e.g. `/users/:id` and `/users/:name`.
+    #[error("ambiguous routes `{method} {pattern}` and `{method} {other}`")]
+    Ambiguous {
+        /// HTTP verb
+        method: Method,
+        /// Route pattern
+        pattern: String,
+        /// The pattern of the overwritten route
+        other: String,
+    },
+    /// The name is given to more than one pattern, the URL of the last one is generated and
+    /// the root router's routes take precedence over the host routers'.
+    #[error("duplicate name `{name}` of `{pattern}` and `{other}`")]
+    DuplicateName {
+        /// Route name
+        name: String,
+        /// Route pattern
+        pattern: String,
+        /// The pattern of the overwritten route
+        other: String,
+    },
+}
+
+/// The conflicts found when building the tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Conflicts(pub Vec<Conflict>);
+
+impl fmt::Display for Conflicts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("conflicting routes:")?;
+        for conflict in &self.0 {
+            write!(f, "\n  {conflict}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Conflicts {}
@@ -0,0 +1,243 @@
+//! Route guards
+
+use std::{fmt, sync::Arc};
+
+use viz_core::{
+    BoxHandler, Handler, HandlerExt, IntoResponse, Method, Request, Response, Result, StatusCode,
+    async_trait,
+    header::{ACCEPT, CONTENT_TYPE, HeaderName},
+};
+
+type Predicate = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// A set of predicates on the request for choosing a handler among the ones sharing
+/// the same HTTP verb and path.
+///
+/// ```
+/// use viz_core::{Request, Result};
+/// use viz_router::{Guard, Router, get};
+///
+/// async fn v1(_: Request) -> Result<&'static str> {
+///     Ok("v1")
+/// }
+///
+/// async fn v2(_: Request) -> Result<&'static str> {
+///     Ok("v2")
+/// }
+///
+/// let app = Router::new()
+///     .route("/users", get(v2).guard(Guard::accept("application/vnd.x.v2+json")))
+///     .get("/users", v1);
+/// ```
+#[derive(Clone)]
+pub struct Guard(Vec<(Predicate, StatusCode)>);
+
+impl Guard {
+    /// Creates a guard by the predicate, `404 Not Found` is responded if no guards match.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        Self(vec![(Arc::new(f), StatusCode::NOT_FOUND)])
+    }
+
+    /// Sets the status responded if no guards match.
+    #[must_use]
+    pub fn rejection(mut self, status: StatusCode) -> Self {
+        for (_, s) in &mut self.0 {
+            *s = status;
+        }
+        self
+    }
+
+    /// Matches the requests with the header equal to the value.
+    pub fn header<V>(name: HeaderName, value: V) -> Self
+    where
+        V: Into<String>,
+    {
+        let value = value.into();
+        Self::new(move |req| {
+            req.headers()
+                .get_all(&name)
+                .iter()
+                .any(|v| v.as_bytes() == value.as_bytes())
+        })
+    }
+
+    /// Matches the requests whose `Accept` header lists the media type, wildcards are not matched.
+    ///
+    /// The media type with `q=0` is not acceptable. `406 Not Acceptable` is responded if no
+    /// guards match.
+    pub fn accept<S>(mime: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let mime = mime.into();
+        Self::new(move |req| {
+            req.headers()
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|v| essence(v).eq_ignore_ascii_case(&mime) && quality(v) > 0.0)
+        })
+        .rejection(StatusCode::NOT_ACCEPTABLE)
+    }
+
+    /// Matches the requests with the `Content-Type` of the media type, the parameters are ignored.
+    ///
+    /// `415 Unsupported Media Type` is responded if no guards match.
+    pub fn content_type<S>(mime: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let mime = mime.into();
+        Self::new(move |req| {
+            req.headers()
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| essence(v).eq_ignore_ascii_case(&mime))
+        })
+        .rejection(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+    }
+
+    /// Matches the requests with the query param.
+    pub fn query<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        Self::new(move |req| {
+            req.uri().query().is_some_and(|query| {
+                query
+                    .split('&')
+                    .any(|pair| pair.split('=').next() == Some(name.as_str()))
+            })
+        })
+    }
+
+    /// Combines the guards, the requests must match both of them.
+    #[must_use]
+    pub fn and(mut self, other: Self) -> Self {
+        self.0.extend(other.0);
+        self
+    }
+
+    /// Returns the status of the first predicate which does not match the request.
+    pub(crate) fn check(&self, req: &Request) -> Result<(), StatusCode> {
+        self.0
+            .iter()
+            .find(|(predicate, _)| !predicate(req))
+            .map_or(Ok(()), |(_, status)| Err(*status))
+    }
+}
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard")
+            .field(
+                "rejections",
+                &self.0.iter().map(|(_, s)| s).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Returns the media type without the parameters.
+fn essence(mime: &str) -> &str {
+    mime.split(';').next().unwrap_or_default().trim()
+}
+
+/// Returns the `q` parameter of the media range, defaults to `1`.
+fn quality(range: &str) -> f32 {
+    range
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(1.0)
+}
+
+/// The status of the rejected request, read by the [`Reject`] handler.
+#[derive(Clone, Copy)]
+struct Rejection(StatusCode);
+
+/// Responds the rejection of the guards, it is wrapped by the middleware of its handler.
+#[derive(Clone, Copy)]
+pub(crate) struct Reject;
+
+#[async_trait]
+impl Handler<Request> for Reject {
+    type Output = Result<Response>;
+
+    async fn call(&self, req: Request) -> Self::Output {
+        let status = req
+            .extensions()
+            .get::<Rejection>()
+            .map_or(StatusCode::NOT_FOUND, |rejection| rejection.0);
+        Err(status.into_error())
+    }
+}
+
+/// A handler with its guard and the handler responding its rejection.
+type Entry = (Option<Guard>, BoxHandler, BoxHandler);
+
+/// Dispatches the requests to the first handler whose guard matches, the handlers without
+/// guards are tried last.
+#[derive(Clone)]
+struct Guarded(Vec<Entry>);
+
+#[async_trait]
+impl Handler<Request> for Guarded {
+    type Output = Result<Response>;
+
+    async fn call(&self, mut req: Request) -> Self::Output {
+        let mut rejected = None;
+        for (guard, handler, reject) in &self.0 {
+            match guard.as_ref().map_or(Ok(()), |guard| guard.check(&req)) {
+                Ok(()) => return handler.call(req).await,
+                Err(status) => {
+                    if rejected.is_none_or(|(s, _)| s == StatusCode::NOT_FOUND) {
+                        rejected = Some((status, reject));
+                    }
+                }
+            }
+        }
+        match rejected {
+            // Goes through the middleware of the handler whose guard rejected the request.
+            Some((status, reject)) => {
+                req.extensions_mut().insert(Rejection(status));
+                reject.call(req).await
+            }
+            None => Err(StatusCode::NOT_FOUND.into_error()),
+        }
+    }
+}
+
+/// Combines the handlers of the same verb into one, which dispatches by their guards.
+pub(crate) fn dispatch(
+    methods: Vec<(Method, BoxHandler)>,
+    guards: Vec<Option<Guard>>,
+    rejections: Vec<BoxHandler>,
+) -> Vec<(Method, BoxHandler)> {
+    let mut grouped = Vec::<(Method, Vec<Entry>)>::new();
+    for (((method, handler), guard), reject) in methods.into_iter().zip(guards).zip(rejections) {
+        match grouped.iter_mut().find(|(m, _)| *m == method) {
+            Some((_, handlers)) => handlers.push((guard, handler, reject)),
+            None => grouped.push((method, vec![(guard, handler, reject)])),
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(method, mut handlers)| {
+            if let [(None, _, _)] = handlers.as_slice() {
+                let (_, handler, _) = handlers.remove(0);
+                return (method, handler);
+            }
+            handlers.sort_by_key(|(guard, _, _)| guard.is_none());
+            (method, Guarded(handlers).boxed())
+        })
+        .collect()
+}
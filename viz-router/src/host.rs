@@ -0,0 +1,101 @@
+//! Host pattern
+
+/// A label of the host pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Label {
+    /// A literal label, matched case-insensitively.
+    Static(String),
+    /// A captured label: `{name}`.
+    Param(String),
+}
+
+/// A pattern for matching the host of requests, e.g. `api.example.com` or `{tenant}.example.com`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HostPattern(Vec<Label>);
+
+impl HostPattern {
+    /// Parses the host pattern.
+    pub(crate) fn new(pattern: &str) -> Self {
+        Self(
+            pattern
+                .trim_end_matches('.')
+                .split('.')
+                .map(|label| {
+                    label
+                        .strip_prefix('{')
+                        .and_then(|l| l.strip_suffix('}'))
+                        .map_or_else(
+                            || Label::Static(label.to_ascii_lowercase()),
+                            |name| Label::Param(name.to_string()),
+                        )
+                })
+                .collect(),
+        )
+    }
+
+    /// Matches the host, the port is ignored.
+    ///
+    /// Returns the captured params if matched.
+    pub(crate) fn matches<'a, 'b>(&'a self, host: &'b str) -> Option<Vec<(&'a str, &'b str)>> {
+        let host = strip_port(host).trim_end_matches('.');
+        let labels = host.split('.').collect::<Vec<_>>();
+
+        if labels.len() != self.0.len() {
+            return None;
+        }
+
+        let mut params = Vec::new();
+        for (label, value) in self.0.iter().zip(labels) {
+            match label {
+                Label::Static(s) => {
+                    if !s.eq_ignore_ascii_case(value) {
+                        return None;
+                    }
+                }
+                Label::Param(name) => {
+                    if value.is_empty() {
+                        return None;
+                    }
+                    params.push((name.as_str(), value));
+                }
+            }
+        }
+        Some(params)
+    }
+}
+
+/// Strips the port of the host, keeps the brackets of IPv6 addresses.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        host.find(']').map_or(host, |i| &host[..=i])
+    } else {
+        host.rsplit_once(':').map_or(host, |(host, _)| host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HostPattern;
+
+    #[test]
+    fn host() {
+        let api = HostPattern::new("api.example.com");
+        assert_eq!(api.matches("api.example.com"), Some(vec![]));
+        assert_eq!(api.matches("API.Example.com:8080"), Some(vec![]));
+        assert_eq!(api.matches("www.example.com"), None);
+        assert_eq!(api.matches("example.com"), None);
+
+        let tenant = HostPattern::new("{tenant}.example.com");
+        assert_eq!(
+            tenant.matches("acme.example.com."),
+            Some(vec![("tenant", "acme")])
+        );
+        assert_eq!(tenant.matches("a.b.example.com"), None);
+        assert_eq!(tenant.matches(".example.com"), None);
+
+        assert_eq!(
+            HostPattern::new("[::1]").matches("[::1]:3000"),
+            Some(vec![])
+        );
+    }
+}
@@ -12,6 +12,17 @@
 #[macro_use]
 pub(crate) mod macros;
 
+mod conflict;
+pub use conflict::{Conflict, Conflicts};
+
+mod guard;
+pub use guard::Guard;
+
+mod host;
+
+mod normalize;
+pub use normalize::{Normalize, Normalized};
+
 mod resources;
 pub use resources::Resources;
 
@@ -21,6 +32,9 @@ pub use route::*;
 mod router;
 pub use router::Router;
 
+mod table;
+pub use table::{RouteEntry, RouteTable};
+
 mod tree;
 pub use tree::Tree;
 
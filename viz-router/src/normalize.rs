@@ -0,0 +1,114 @@
+//! Path normalization
+
+use std::borrow::Cow;
+
+use percent_encoding::percent_decode_str;
+
+/// The policy for the request paths which do not match any routes as is, such as
+/// `/users/`, `//users` or `/posts/../users` when `/users` is registered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Normalize {
+    /// Matches the request paths as is, only percent-decoded.
+    #[default]
+    Strict,
+    /// Redirects to the canonical path with `308 Permanent Redirect`.
+    Redirect,
+    /// Matches the canonical path silently.
+    Match,
+}
+
+/// The result of normalizing a request path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Normalized {
+    /// Redirects to the canonical path.
+    Redirect(String),
+    /// Looks up the routes by the rewritten path.
+    Rewrite(String),
+}
+
+/// Collapses the repeated slashes and resolves the `.` and `..` segments.
+pub(crate) fn canonicalize(path: &str) -> String {
+    let mut segments = Vec::new();
+    let mut trailing = false;
+
+    for segment in path.split('/') {
+        trailing = segment.is_empty() || segment == "." || segment == "..";
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+
+    let mut canonical = String::with_capacity(path.len());
+    for segment in &segments {
+        canonical.push('/');
+        canonical.push_str(segment);
+    }
+    if trailing || canonical.is_empty() {
+        canonical.push('/');
+    }
+    canonical
+}
+
+/// Toggles the trailing slash of the path, returns `None` for the root path.
+pub(crate) fn toggle_trailing_slash(path: &str) -> Option<String> {
+    if path == "/" {
+        None
+    } else if let Some(path) = path.strip_suffix('/') {
+        Some(path.to_string())
+    } else {
+        Some(format!("{path}/"))
+    }
+}
+
+/// Percent-decodes each segment of the path.
+///
+/// The segments which contain an encoded `/`, decode to a `.` or `..` segment or are not valid
+/// UTF-8 are kept as is, so the decoded path has the same segments.
+pub(crate) fn decode(path: &str) -> Cow<'_, str> {
+    if !path.contains('%') {
+        return Cow::Borrowed(path);
+    }
+
+    Cow::Owned(
+        path.split('/')
+            .map(|segment| {
+                percent_decode_str(segment)
+                    .decode_utf8()
+                    .ok()
+                    .filter(|s| !s.contains('/') && s != "." && s != "..")
+                    .unwrap_or(Cow::Borrowed(segment))
+            })
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, decode, toggle_trailing_slash};
+
+    #[test]
+    fn normalize() {
+        assert_eq!(canonicalize("/"), "/");
+        assert_eq!(canonicalize("//"), "/");
+        assert_eq!(canonicalize("/users"), "/users");
+        assert_eq!(canonicalize("/users/"), "/users/");
+        assert_eq!(canonicalize("//users//1"), "/users/1");
+        assert_eq!(canonicalize("/posts/../users/./1"), "/users/1");
+        assert_eq!(canonicalize("/users/1/.."), "/users/");
+        assert_eq!(canonicalize("/../.."), "/");
+
+        assert_eq!(toggle_trailing_slash("/"), None);
+        assert_eq!(toggle_trailing_slash("/users"), Some("/users/".to_string()));
+        assert_eq!(toggle_trailing_slash("/users/"), Some("/users".to_string()));
+
+        assert_eq!(decode("/users"), "/users");
+        assert_eq!(decode("/caf%C3%A9/a%20b"), "/café/a b");
+        assert_eq!(decode("/a%2Fb/%FF"), "/a%2Fb/%FF");
+        assert_eq!(decode("/%2E%2E/%2e/x"), "/%2E%2E/%2e/x");
+    }
+}
@@ -32,6 +32,9 @@ pub struct Resources {
 
 impl Resources {
     /// Named for the resources.
+    ///
+    /// The routes are also named `{name}.index`, `{name}.new`, `{name}.show` and `{name}.edit`
+    /// unless they are named explicitly.
     #[must_use]
     pub fn named<S>(mut self, name: S) -> Self
     where
@@ -61,7 +64,7 @@ impl Resources {
             .find(|(p, _)| p == &kind)
             .map(|(_, r)| r)
         {
-            Some(r) => *r = route.into_iter().fold(r.clone(), |r, (m, h)| r.on(m, h)),
+            Some(r) => *r = r.clone().merge(route),
             None => {
                 self.routes.push((kind, route));
             }
@@ -182,15 +185,7 @@ impl Resources {
             routes: self
                 .routes
                 .into_iter()
-                .map(|(path, route)| {
-                    (
-                        path,
-                        route
-                            .into_iter()
-                            .map(|(method, handler)| (method, f(handler)))
-                            .collect(),
-                    )
-                })
+                .map(|(path, route)| (path, route.map_handler(&f)))
                 .collect(),
         }
     }
@@ -223,7 +218,16 @@ impl IntoIterator for Resources {
     fn into_iter(self) -> Self::IntoIter {
         self.routes
             .into_iter()
-            .map(|(kind, route)| {
+            .map(|(kind, mut route)| {
+                if route.name.is_none() && !self.name.is_empty() {
+                    route.name = match kind {
+                        Kind::Empty => Some(format!("{}.index", self.name)),
+                        Kind::New => Some(format!("{}.new", self.name)),
+                        Kind::Id => Some(format!("{}.show", self.name)),
+                        Kind::Edit => Some(format!("{}.edit", self.name)),
+                        Kind::Custom(_) => None,
+                    };
+                }
                 (
                     match kind {
                         Kind::Empty => String::new(),
@@ -7,6 +7,11 @@ use viz_core::{
     Transform,
 };
 
+use crate::{
+    Guard,
+    guard::{Reject, dispatch},
+};
+
 macro_rules! export_internal_verb {
     ($name:ident $verb:tt) => {
         #[doc = concat!(" Appends a handler buy the HTTP `", stringify!($verb), "` verb into the route.")]
@@ -38,7 +43,17 @@ macro_rules! export_verb {
 /// A collection of verb-handler pair.
 #[derive(Clone, Default)]
 pub struct Route {
+    pub(crate) name: Option<String>,
     pub(crate) methods: Vec<(Method, BoxHandler)>,
+    /// The number of middleware wrapping each handler, in the order of `methods`.
+    pub(crate) middleware: Vec<usize>,
+    /// The guard of each handler, in the order of `methods`.
+    pub(crate) guards: Vec<Option<Guard>>,
+    /// The handler responding the rejection of each guard, wrapped by the same middleware as
+    /// its handler, in the order of `methods`.
+    pub(crate) rejections: Vec<BoxHandler>,
+    /// The synthesized `OPTIONS` handler, which is only used if there is no explicit one.
+    pub(crate) auto_options: Option<BoxHandler>,
 }
 
 impl Route {
@@ -46,26 +61,129 @@ impl Route {
     #[must_use]
     pub const fn new() -> Self {
         Self {
+            name: None,
             methods: Vec::new(),
+            middleware: Vec::new(),
+            guards: Vec::new(),
+            rejections: Vec::new(),
+            auto_options: None,
         }
     }
 
-    /// Appends a HTTP verb and handler pair into the route.
+    /// Names the route for generating its URL.
     #[must_use]
-    pub fn push(mut self, method: Method, handler: BoxHandler) -> Self {
-        match self
+    pub fn name<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.name.replace(name.into());
+        self
+    }
+
+    /// Guards the handlers of the route, the guard is combined with the existing ones.
+    ///
+    /// The handlers sharing the same verb and path are chosen by their guards in order,
+    /// the one without guards is chosen last. If no guards match, the status of the first
+    /// unmatched guard other than `404 Not Found`, e.g. `406 Not Acceptable` or
+    /// `415 Unsupported Media Type`, is responded through the middleware of its handler.
+    #[must_use]
+    pub fn guard(self, guard: Guard) -> Self {
+        Self {
+            guards: self
+                .guards
+                .into_iter()
+                .map(|g| Some(g.map_or_else(|| guard.clone(), |g| g.and(guard.clone()))))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Merges the verb-handler pairs and the name of the other route into the route.
+    #[must_use]
+    pub(crate) fn merge(self, other: Self) -> Self {
+        let Self {
+            name,
+            methods,
+            middleware,
+            guards,
+            rejections,
+            auto_options,
+        } = other;
+        let mut route = methods
+            .into_iter()
+            .zip(middleware)
+            .zip(guards)
+            .zip(rejections)
+            .fold(
+                self,
+                |mut route, ((((method, handler), middleware), guard), reject)| {
+                    route.insert(method, handler, middleware, guard, reject);
+                    route
+                },
+            );
+        if name.is_some() {
+            route.name = name;
+        }
+        if auto_options.is_some() {
+            route.auto_options = auto_options;
+        }
+        route
+    }
+
+    /// Returns the verbs which have unguarded handlers in both of the routes.
+    pub(crate) fn duplicates<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a Method> {
+        let unguarded = |route: &'a Self| {
+            route
+                .methods
+                .iter()
+                .zip(&route.guards)
+                .filter(|(_, g)| g.is_none())
+                .map(|((m, _), _)| m)
+        };
+        unguarded(other).filter(move |m| unguarded(self).any(|n| n == *m))
+    }
+
+    /// Replaces the unguarded handler of the verb, the guarded handlers are appended.
+    fn insert(
+        &mut self,
+        method: Method,
+        handler: BoxHandler,
+        middleware: usize,
+        guard: Option<Guard>,
+        reject: BoxHandler,
+    ) {
+        if let Some(i) = self
             .methods
-            .iter_mut()
-            .find(|(m, _)| m == method)
-            .map(|(_, e)| e)
+            .iter()
+            .zip(&self.guards)
+            .position(|((m, _), g)| *m == method && g.is_none() && guard.is_none())
         {
-            Some(h) => *h = handler,
-            None => self.methods.push((method, handler)),
+            self.methods[i].1 = handler;
+            self.middleware[i] = middleware;
+            self.rejections[i] = reject;
+        } else {
+            self.methods.push((method, handler));
+            self.middleware.push(middleware);
+            self.guards.push(guard);
+            self.rejections.push(reject);
         }
+    }
 
+    /// Appends a HTTP verb and handler pair into the route.
+    #[must_use]
+    pub fn push(mut self, method: Method, handler: BoxHandler) -> Self {
+        self.insert(method, handler, 0, None, Reject.boxed());
         self
     }
 
+    /// Returns the verbs and the number of middleware wrapping their handlers.
+    pub fn methods(&self) -> impl Iterator<Item = (&Method, usize)> {
+        self.methods
+            .iter()
+            .map(|(m, _)| m)
+            .zip(self.middleware.iter().copied())
+    }
+
     /// Appends a handler by the specified HTTP verb into the route.
     #[must_use]
     pub fn on<H, O>(self, method: Method, handler: H) -> Self
@@ -117,9 +235,17 @@ impl Route {
     where
         F: Fn(BoxHandler) -> BoxHandler,
     {
-        self.into_iter()
-            .map(|(method, handler)| (method, f(handler)))
-            .collect()
+        Self {
+            methods: self
+                .methods
+                .into_iter()
+                .map(|(method, handler)| (method, f(handler)))
+                .collect(),
+            middleware: self.middleware.into_iter().map(|n| n + 1).collect(),
+            rejections: self.rejections.into_iter().map(&f).collect(),
+            auto_options: self.auto_options.map(&f),
+            ..self
+        }
     }
 
     /// Transforms the types to a middleware and adds it.
@@ -147,8 +273,9 @@ impl IntoIterator for Route {
 
     type IntoIter = std::vec::IntoIter<(Method, BoxHandler)>;
 
+    /// The guarded handlers of the same verb are combined into one.
     fn into_iter(self) -> Self::IntoIter {
-        self.methods.into_iter()
+        dispatch(self.methods, self.guards, self.rejections).into_iter()
     }
 }
 
@@ -157,8 +284,14 @@ impl FromIterator<(Method, BoxHandler)> for Route {
     where
         T: IntoIterator<Item = (Method, BoxHandler)>,
     {
+        let methods = iter.into_iter().collect::<Vec<_>>();
         Self {
-            methods: iter.into_iter().collect(),
+            name: None,
+            middleware: vec![0; methods.len()],
+            guards: vec![None; methods.len()],
+            rejections: vec![Reject.boxed(); methods.len()],
+            methods,
+            auto_options: None,
         }
     }
 }
@@ -197,6 +330,7 @@ where
 impl fmt::Debug for Route {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Route")
+            .field("name", &self.name)
             .field(
                 "methods",
                 &self
@@ -205,7 +339,10 @@ impl fmt::Debug for Route {
                     .map(|(m, _)| m)
                     .collect::<Vec<&Method>>(),
             )
-            .finish()
+            .field("middleware", &self.middleware)
+            .field("guards", &self.guards)
+            .field("auto_options", &self.auto_options.is_some())
+            .finish_non_exhaustive()
     }
 }
 
@@ -1,8 +1,9 @@
 use viz_core::{
-    BoxHandler, Handler, HandlerExt, IntoResponse, Next, Request, Response, Result, Transform,
+    BoxHandler, Handler, HandlerExt, IntoResponse, Method, Next, Request, Response, Result,
+    StatusCode, Transform,
 };
 
-use crate::{Resources, Route};
+use crate::{Normalize, Resources, Route, RouteEntry, RouteTable};
 
 macro_rules! export_verb {
     ($name:ident $verb:ty) => {
@@ -23,13 +24,27 @@ macro_rules! export_verb {
 #[derive(Clone, Debug, Default)]
 pub struct Router {
     pub(crate) routes: Option<Vec<(String, Route)>>,
+    pub(crate) fallbacks: Vec<(String, BoxHandler)>,
+    pub(crate) normalize: Normalize,
+    pub(crate) hosts: Vec<(String, Router)>,
+    pub(crate) duplicates: Vec<(Method, String)>,
+    pub(crate) strict: bool,
+    pub(crate) auto_options: bool,
 }
 
 impl Router {
     /// Creates an empty `Router`.
     #[must_use]
     pub const fn new() -> Self {
-        Self { routes: None }
+        Self {
+            routes: None,
+            fallbacks: Vec::new(),
+            normalize: Normalize::Strict,
+            hosts: Vec::new(),
+            duplicates: Vec::new(),
+            strict: false,
+            auto_options: false,
+        }
     }
 
     fn push<S>(routes: &mut Vec<(String, Route)>, path: S, route: Route)
@@ -42,27 +57,31 @@ impl Router {
             .find_map(|(p, r)| if p == path { Some(r) } else { None })
         {
             Some(r) => {
-                *r = route.into_iter().fold(
-                    // original route
-                    r.clone().into_iter().collect(),
-                    |or: Route, (method, handler)| or.on(method, handler),
-                );
+                *r = r.clone().merge(route);
             }
             None => routes.push((path.to_string(), route)),
         }
     }
 
     /// Inserts a path-route pair into the router.
+    ///
+    /// The route is merged into the existing one with the same path, the handlers of the same
+    /// verbs are overwritten and reported as [duplicates](crate::Conflict::Duplicate).
     #[must_use]
-    pub fn route<S>(mut self, path: S, route: Route) -> Self
+    pub fn route<S>(mut self, path: S, mut route: Route) -> Self
     where
         S: AsRef<str>,
     {
-        Self::push(
-            self.routes.get_or_insert_with(Vec::new),
-            path.as_ref().trim_start_matches('/'),
-            route,
-        );
+        if self.auto_options && route.auto_options.is_none() {
+            route.auto_options = Some(auto_options_handler());
+        }
+        let path = path.as_ref().trim_start_matches('/');
+        let routes = self.routes.get_or_insert_with(Vec::new);
+        if let Some((_, r)) = routes.iter().find(|(p, _)| p == path) {
+            self.duplicates
+                .extend(r.duplicates(&route).map(|m| (m.clone(), path.to_string())));
+        }
+        Self::push(routes, path, route);
         self
     }
 
@@ -99,19 +118,112 @@ impl Router {
             path.push('/');
         }
 
-        match router.routes {
-            Some(routes) => routes.into_iter().fold(self, |router, (mut sp, route)| {
+        let Self {
+            routes,
+            fallbacks,
+            hosts,
+            duplicates,
+            ..
+        } = router;
+
+        let mut router = hosts.into_iter().fold(self, |router, (host, sub)| {
+            router.host(host, Self::new().nest(&path, sub))
+        });
+
+        router
+            .duplicates
+            .extend(duplicates.into_iter().map(|(method, sp)| {
+                let is_empty = sp.is_empty();
+                let mut sp = path.clone() + &sp;
+                if is_empty {
+                    sp = sp.trim_end_matches('/').to_string();
+                }
+                (method, sp.trim_start_matches('/').to_string())
+            }));
+
+        router = fallbacks
+            .into_iter()
+            .fold(router, |mut router, (mut sp, handler)| {
+                sp = path.clone() + &sp;
+                Self::push_fallback(
+                    &mut router.fallbacks,
+                    sp.trim_matches('/').to_string(),
+                    handler,
+                );
+                router
+            });
+
+        if let Some(routes) = routes {
+            router = routes.into_iter().fold(router, |router, (mut sp, route)| {
                 let is_empty = sp.is_empty();
                 sp = path.clone() + &sp;
                 if is_empty {
                     sp = sp.trim_end_matches('/').to_string();
                 }
                 router.route(sp, route)
-            }),
-            None => self,
+            });
+        }
+
+        router
+    }
+
+    /// Merges the routes, fallbacks and host routers of the other router into the router.
+    ///
+    /// The handlers of the same verbs and paths are overwritten by the other router and
+    /// reported as [duplicates](crate::Conflict::Duplicate).
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        self.nest("", other)
+    }
+
+    fn push_fallback(fallbacks: &mut Vec<(String, BoxHandler)>, path: String, handler: BoxHandler) {
+        match fallbacks.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, h)) => *h = handler,
+            None => fallbacks.push((path, handler)),
         }
     }
 
+    /// Sets a handler for the requests which do not match any routes.
+    ///
+    /// When the router is nested, the fallback only handles the requests under the nested path.
+    /// Requests matching a path with other verbs are still responded `405 Method Not Allowed`.
+    #[must_use]
+    pub fn fallback<H, O>(mut self, handler: H) -> Self
+    where
+        H: Handler<Request, Output = Result<O>> + Clone,
+        O: IntoResponse + Send + 'static,
+    {
+        Self::push_fallback(
+            &mut self.fallbacks,
+            String::new(),
+            handler.map_into_response().boxed(),
+        );
+        self
+    }
+
+    /// Adds a sub-router for the requests to the host, the port of the host is ignored.
+    ///
+    /// A label of the host can be captured by `{name}`, e.g. `{tenant}.example.com`,
+    /// the captured values are prepended to the path params. The host routers are matched
+    /// in order before the routes of the router, only the ones of the root router are applied.
+    #[must_use]
+    pub fn host<S>(mut self, host: S, router: Self) -> Self
+    where
+        S: Into<String>,
+    {
+        let host = host.into();
+        let router = if self.auto_options {
+            router.auto_options()
+        } else {
+            router
+        };
+        match self.hosts.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, r)) => *r = std::mem::take(r).nest("", router),
+            None => self.hosts.push((host, router)),
+        }
+        self
+    }
+
     repeat!(
         export_verb
         get GET
@@ -136,6 +248,95 @@ impl Router {
         self.route(path, Route::new().any(handler))
     }
 
+    /// Sets the policy for the request paths which do not match any routes as is.
+    ///
+    /// The repeated slashes, the `.` and `..` segments and the trailing slash are normalized,
+    /// except in the [`Normalize::Strict`] policy. The path is percent-decoded before matching
+    /// under every policy. Only the policy of the root router is applied.
+    #[must_use]
+    pub const fn normalize(mut self, normalize: Normalize) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Enables the strict mode, which rejects the router if any routes conflict.
+    ///
+    /// Converting it into a [`Tree`] panics, while [`Tree::try_build`] returns the conflicts,
+    /// e.g. starting or swapping the routes of a server fails without panicking.
+    ///
+    /// Only the strict mode of the root router is applied.
+    ///
+    /// [`Tree`]: crate::Tree
+    /// [`Tree::try_build`]: crate::Tree::try_build
+    #[must_use]
+    pub const fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Synthesizes the `OPTIONS` handlers for the routes, including the ones added later.
+    ///
+    /// Each synthesized handler responds `204 No Content` with the `Allow` header listing
+    /// the verbs of its path when the [`Tree`] is built. An explicit `OPTIONS` handler of the
+    /// path takes precedence, the synthesized ones are not reported as duplicates.
+    ///
+    /// Calls it before adding middleware, e.g. the CORS middleware, so that the synthesized
+    /// handlers are wrapped and preflight requests are still answered by the middleware.
+    ///
+    /// [`Tree`]: crate::Tree
+    #[must_use]
+    pub fn auto_options(self) -> Self {
+        Self {
+            routes: self.routes.map(|routes| {
+                routes
+                    .into_iter()
+                    .map(|(path, mut route)| {
+                        if route.auto_options.is_none() {
+                            route.auto_options = Some(auto_options_handler());
+                        }
+                        (path, route)
+                    })
+                    .collect()
+            }),
+            hosts: self
+                .hosts
+                .into_iter()
+                .map(|(host, router)| (host, router.auto_options()))
+                .collect(),
+            auto_options: true,
+            ..self
+        }
+    }
+
+    /// Returns an iterator over the registered routes.
+    ///
+    /// The patterns of the host routers are prefixed with the hosts.
+    pub fn routes(&self) -> impl Iterator<Item = RouteEntry> + '_ {
+        self.routes
+            .iter()
+            .flatten()
+            .flat_map(|(path, route)| {
+                route.methods().map(move |(method, middleware)| RouteEntry {
+                    method: method.clone(),
+                    pattern: format!("/{}", path.trim_start_matches('/')),
+                    name: route.name.clone(),
+                    middleware,
+                })
+            })
+            .chain(self.hosts.iter().flat_map(|(host, router)| {
+                router.table().into_iter().map(move |entry| RouteEntry {
+                    pattern: format!("{host}{}", entry.pattern),
+                    ..entry
+                })
+            }))
+    }
+
+    /// Returns the registered routes as a table.
+    #[must_use]
+    pub fn table(&self) -> RouteTable {
+        self.routes().collect()
+    }
+
     /// Takes a closure and creates an iterator which calls that closure on each handler.
     #[must_use]
     pub fn map_handler<F>(self, f: F) -> Self
@@ -146,17 +347,25 @@ impl Router {
             routes: self.routes.map(|routes| {
                 routes
                     .into_iter()
-                    .map(|(path, route)| {
-                        (
-                            path,
-                            route
-                                .into_iter()
-                                .map(|(method, handler)| (method, f(handler)))
-                                .collect(),
-                        )
-                    })
+                    .map(|(path, route)| (path, route.map_handler(&f)))
                     .collect()
             }),
+            fallbacks: self
+                .fallbacks
+                .into_iter()
+                .map(|(path, handler)| (path, f(handler)))
+                .collect(),
+            hosts: self
+                .hosts
+                .into_iter()
+                .map(|(host, router)| {
+                    (
+                        host,
+                        router.map_handler(&f as &dyn Fn(BoxHandler) -> BoxHandler),
+                    )
+                })
+                .collect(),
+            ..self
         }
     }
 
@@ -180,6 +389,12 @@ impl Router {
     }
 }
 
+/// Responds `204 No Content`, the `Allow` header is added when the [`Tree`](crate::Tree) is
+/// built.
+fn auto_options_handler() -> BoxHandler {
+    (|_: Request| async { Ok(StatusCode::NO_CONTENT.into_response()) }).boxed()
+}
+
 #[cfg(test)]
 #[allow(clippy::unused_async)]
 mod tests {
@@ -188,10 +403,14 @@ mod tests {
     use viz_core::{
         Body, Error, Handler, HandlerExt, IntoResponse, Method, Next, Request, RequestExt,
         Response, ResponseExt, Result, StatusCode, Transform, async_trait,
+        headers::{Allow, HeaderMapExt},
         types::{Params, RouteInfo},
     };
 
-    use crate::{Resources, Route, Router, Tree, any, get};
+    use crate::{
+        Conflict, Conflicts, Guard, Normalize, Normalized, Resources, Route, RouteEntry, Router,
+        Tree, any, get, post,
+    };
 
     #[derive(Clone)]
     struct Logger;
@@ -522,6 +741,487 @@ mod tests {
         );
     }
 
+    #[test]
+    fn allowed() {
+        let tree: Tree = Router::new()
+            .get("/users", |_: Request| async { Ok(()) })
+            .post("/users", |_: Request| async { Ok(()) })
+            .delete("/users/:id", |_: Request| async { Ok(()) })
+            .into();
+
+        assert_eq!(
+            tree.allowed("/users"),
+            vec![Method::GET, Method::POST, Method::HEAD]
+        );
+        assert_eq!(tree.allowed("/users/1"), vec![Method::DELETE]);
+        assert!(tree.allowed("/posts").is_empty());
+    }
+
+    #[tokio::test]
+    async fn auto_options() -> anyhow::Result<()> {
+        let router = Router::new()
+            .get("/users", |_: Request| async { Ok(()) })
+            .auto_options()
+            .delete("/users/:id", |_: Request| async { Ok(()) })
+            // The explicit handler is not a duplicate of the synthesized one.
+            .options("/users/:id", |_: Request| async { Ok("explicit") });
+        let tree = Tree::build(
+            router
+                .merge(Router::new().post("/users", |_: Request| async { Ok(()) }))
+                .strict(),
+        )?;
+        assert_eq!(tree.routes().count(), 4);
+
+        let (req, method, path) = client(Method::OPTIONS, "/users");
+        let (h, _) = tree.find(&method, &path).unwrap();
+        let res = h.call(req).await?;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            res.headers()
+                .typed_get::<Allow>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Method::GET, Method::POST, Method::HEAD, Method::OPTIONS]
+        );
+
+        let (req, method, path) = client(Method::OPTIONS, "/users/1");
+        let (h, _) = tree.find(&method, &path).unwrap();
+        assert_eq!(
+            h.call(req).await?.into_body().collect().await?.to_bytes(),
+            "explicit"
+        );
+
+        assert!(tree.find(&Method::OPTIONS, "/posts").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fallback() -> anyhow::Result<()> {
+        let api = Router::new()
+            .get("/users", |_: Request| async { Ok("users") })
+            .fallback(|_: Request| async { Ok("api fallback") });
+
+        let tree: Tree = Router::new()
+            .get("/", |_: Request| async { Ok("index") })
+            .nest("/api", api)
+            .fallback(|_: Request| async { Ok("fallback") })
+            .with(Logger::new())
+            .into();
+
+        for (path, body) in [
+            ("/", "fallback"),
+            ("/posts", "fallback"),
+            ("/api", "api fallback"),
+            ("/api/posts/1", "api fallback"),
+            ("/apis", "fallback"),
+        ] {
+            let (req, _, path) = client(Method::GET, path);
+            let (h, _) = tree.fallback(&path).unwrap();
+            assert_eq!(
+                h.call(req).await?.into_body().collect().await?.to_bytes(),
+                body
+            );
+        }
+
+        assert!(Tree::from(Router::new()).fallback("/").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn guard() -> anyhow::Result<()> {
+        use viz_core::header::{ACCEPT, CONTENT_TYPE};
+
+        let tree: Tree = Router::new()
+            .route(
+                "/users",
+                get(|_: Request| async { Ok("v2") })
+                    .guard(Guard::accept("application/vnd.x.v2+json")),
+            )
+            .get("/users", |_: Request| async { Ok("v1") })
+            .route(
+                "/posts",
+                post(|_: Request| async { Ok("json draft") })
+                    .guard(Guard::content_type("application/json"))
+                    .guard(Guard::query("draft")),
+            )
+            .route(
+                "/posts",
+                post(|_: Request| async { Ok("json") })
+                    .guard(Guard::content_type("application/json")),
+            )
+            .route(
+                "/tags",
+                get(|_: Request| async { Ok("json") }).guard(Guard::accept("application/json")),
+            )
+            .with(Logger::new())
+            .into();
+
+        for (method, path, header, body) in [
+            (Method::GET, "/users", None, "v1"),
+            (
+                Method::GET,
+                "/users",
+                Some((ACCEPT, "text/html, application/vnd.x.v2+json;q=0.9")),
+                "v2",
+            ),
+            (
+                Method::POST,
+                "/posts?draft",
+                Some((CONTENT_TYPE, "application/json; charset=utf-8")),
+                "json draft",
+            ),
+            (
+                Method::POST,
+                "/posts",
+                Some((CONTENT_TYPE, "application/json")),
+                "json",
+            ),
+        ] {
+            let mut builder = Request::builder().method(method.clone()).uri(path);
+            if let Some((name, value)) = header {
+                builder = builder.header(name, value);
+            }
+            let req = builder.body(Body::Empty)?;
+            let (h, _) = tree.find(&method, req.uri().path()).unwrap();
+            assert_eq!(
+                h.call(req).await?.into_body().collect().await?.to_bytes(),
+                body
+            );
+        }
+
+        for (method, path, status) in [
+            (Method::GET, "/tags", StatusCode::NOT_ACCEPTABLE),
+            (Method::POST, "/posts", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ] {
+            let (req, method, _) = client(method, path);
+            let (h, _) = tree.find(&method, path).unwrap();
+            assert_eq!(
+                h.call(req).await.unwrap_err().into_response().status(),
+                status
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge() {
+        let users = Router::new()
+            .get("/users", |_: Request| async { Ok("users") })
+            .get("/users/:id", |_: Request| async { Ok("user") });
+
+        let posts = Router::new()
+            .get("/posts", |_: Request| async { Ok("posts") })
+            .post("/users", |_: Request| async { Ok("create user") })
+            .route(
+                "/users",
+                get(|_: Request| async { Ok("v2 users") }).guard(Guard::query("v2")),
+            )
+            .get("/users", |_: Request| async { Ok("users again") })
+            .get("/users/:name", |_: Request| async { Ok("user again") });
+
+        let router = Router::new()
+            .merge(users)
+            .nest("/api", Router::new().merge(posts));
+
+        assert!(Tree::build(router.clone().nest("/api", Router::new())).is_ok());
+
+        assert_eq!(
+            Tree::build(
+                router
+                    .get("/posts", |_: Request| async { Ok("posts") })
+                    .merge(Router::new().get("/posts", |_: Request| async { Ok("posts") }))
+                    .get("/posts/:id", |_: Request| async { Ok("post") })
+                    .get("/posts/:slug", |_: Request| async { Ok("post") })
+            )
+            .unwrap_err(),
+            Conflicts(vec![
+                Conflict::Duplicate {
+                    method: Method::GET,
+                    pattern: "/posts".to_string(),
+                },
+                Conflict::Ambiguous {
+                    method: Method::GET,
+                    pattern: "/posts/:slug".to_string(),
+                    other: "/posts/:id".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate route `POST /api/users`")]
+    fn strict() {
+        let users = Router::new().post("/users", |_: Request| async { Ok("create user") });
+
+        let _: Tree = Router::new()
+            .nest("/api", users.clone())
+            .nest("/api", users)
+            .strict()
+            .into();
+    }
+
+    #[test]
+    fn try_build() {
+        let users = Router::new().post("/users", |_: Request| async { Ok("create user") });
+        let router = Router::new()
+            .nest("/api", users.clone())
+            .nest("/api", users);
+
+        assert!(Tree::try_build(router.clone()).is_ok());
+        assert_eq!(
+            Tree::try_build(router.strict()).unwrap_err().0,
+            vec![Conflict::Duplicate {
+                method: Method::POST,
+                pattern: "/api/users".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let handler = |_: Request| async { Ok("") };
+        let router = Router::new()
+            .route("/users", get(handler).post(handler).name("users"))
+            .route("/people", get(handler).name("users"))
+            .host(
+                "api.example.com",
+                Router::new()
+                    .route("/users", get(handler).name("users"))
+                    .route("/posts", get(handler).name("posts"))
+                    .route("/articles", get(handler).name("posts")),
+            );
+
+        assert_eq!(
+            Tree::build(router.clone()).unwrap_err().0,
+            vec![
+                Conflict::DuplicateName {
+                    name: "posts".to_string(),
+                    pattern: "api.example.com/articles".to_string(),
+                    other: "api.example.com/posts".to_string(),
+                },
+                Conflict::DuplicateName {
+                    name: "users".to_string(),
+                    pattern: "/users".to_string(),
+                    other: "api.example.com/users".to_string(),
+                },
+                Conflict::DuplicateName {
+                    name: "users".to_string(),
+                    pattern: "/people".to_string(),
+                    other: "/users".to_string(),
+                },
+            ]
+        );
+        assert!(Tree::try_build(router.clone()).is_ok());
+        assert!(Tree::try_build(router.strict()).is_err());
+    }
+
+    #[tokio::test]
+    async fn host() -> anyhow::Result<()> {
+        let api = Router::new().get("/users", |_: Request| async { Ok("api users") });
+        let tenant = Router::new().get("/users/:id", |_: Request| async { Ok("tenant user") });
+
+        let router = Router::new()
+            .get("/users", |_: Request| async { Ok("users") })
+            .host("api.example.com", api)
+            .nest("/v1", Router::new().host("{tenant}.example.com", tenant))
+            .with(Logger::new());
+
+        assert_eq!(
+            router
+                .routes()
+                .map(|e| (e.pattern, e.middleware))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/users".to_string(), 1),
+                ("api.example.com/users".to_string(), 1),
+                ("{tenant}.example.com/v1/users/:id".to_string(), 1),
+            ]
+        );
+
+        let tree: Tree = router.into();
+
+        for (host, path, body, params) in [
+            (None, "/users", "users", vec![]),
+            (Some("example.com"), "/users", "users", vec![]),
+            (Some("api.example.com:3000"), "/users", "api users", vec![]),
+            (
+                Some("acme.example.com"),
+                "/v1/users/1",
+                "tenant user",
+                vec![("tenant", "acme")],
+            ),
+        ] {
+            let (tree, captured) = tree.select(host);
+            assert_eq!(captured, params);
+            let (req, method, path) = client(Method::GET, path);
+            let (h, _) = tree.find(&method, &path).unwrap();
+            assert_eq!(
+                h.call(req).await?.into_body().collect().await?.to_bytes(),
+                body
+            );
+        }
+
+        let (tree, _) = tree.select(Some("api.example.com"));
+        assert!(tree.find(&Method::GET, "/v1/users/1").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalize() {
+        let router = Router::new()
+            .get("/users", |_: Request| async { Ok(()) })
+            .get("/posts/", |_: Request| async { Ok(()) })
+            .get("/tags/:name", |_: Request| async { Ok(()) });
+
+        let tree: Tree = router.clone().into();
+        assert_eq!(tree.normalize("/users/"), None);
+        assert_eq!(
+            tree.normalize("/tags/caf%C3%A9"),
+            Some(Normalized::Rewrite("/tags/café".to_string()))
+        );
+
+        let tree: Tree = router.clone().normalize(Normalize::Redirect).into();
+        assert_eq!(tree.normalize("/users"), None);
+        assert_eq!(
+            tree.normalize("/users/"),
+            Some(Normalized::Redirect("/users".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("//posts"),
+            Some(Normalized::Redirect("/posts/".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("/tags/../users"),
+            Some(Normalized::Redirect("/users".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("/tags/caf%C3%A9"),
+            Some(Normalized::Rewrite("/tags/café".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("/tags/caf%C3%A9/"),
+            Some(Normalized::Redirect("/tags/caf%C3%A9".to_string()))
+        );
+        assert_eq!(tree.normalize("/comments/"), None);
+
+        let tree: Tree = router.normalize(Normalize::Match).into();
+        assert_eq!(
+            tree.normalize("/users/"),
+            Some(Normalized::Rewrite("/users".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("//tags//caf%C3%A9/"),
+            Some(Normalized::Rewrite("/tags/café".to_string()))
+        );
+        assert_eq!(
+            tree.normalize("/tags/caf%C3%A9"),
+            Some(Normalized::Rewrite("/tags/café".to_string()))
+        );
+        // An encoded `/` is kept.
+        assert_eq!(tree.normalize("/tags/a%2Fb"), None);
+        // The encoded dot segments are neither decoded nor resolved.
+        assert_eq!(tree.normalize("/tags/%2E%2E"), None);
+        assert_eq!(tree.normalize("/%2E%2E/users"), None);
+    }
+
+    #[test]
+    fn url_for() {
+        let handler = |_: Request| async { Ok(()) };
+
+        let users = Resources::default()
+            .named("user")
+            .route("search", get(handler).name("user.search"))
+            .index(handler)
+            .show(handler)
+            .edit(handler);
+
+        let tree: Tree = Router::new()
+            .route("/", get(handler).name("home"))
+            .nest("/api", Router::new().resources("users", users))
+            .post("/", handler)
+            .into();
+
+        let urls = tree.urls();
+        assert_eq!(urls.url_for("home", [("page", 1)]).unwrap(), "/?page=1");
+        assert_eq!(
+            urls.url_for("user.index", Vec::<(&str, &str)>::new())
+                .unwrap(),
+            "/api/users"
+        );
+        assert_eq!(
+            urls.url_for("user.search", [("q", "viz")]).unwrap(),
+            "/api/users/search?q=viz"
+        );
+        assert_eq!(
+            urls.url_for("user.show", [("user_id", 1)]).unwrap(),
+            "/api/users/1"
+        );
+        assert_eq!(
+            urls.url_for("user.edit", [("user_id", 1)]).unwrap(),
+            "/api/users/1/edit"
+        );
+        assert!(!urls.contains("user.new"));
+    }
+
+    #[test]
+    fn routes() {
+        let handler = |_: Request| async { Ok(()) };
+
+        let users = Router::new()
+            .route("/:id", get(handler).name("user.show"))
+            .with(Logger::new())
+            .post("/:id", handler);
+
+        let router = Router::new()
+            .route("/", get(handler).name("home"))
+            .nest("/users", users)
+            .with(Logger::new());
+
+        let entries = router.routes().collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                RouteEntry {
+                    method: Method::GET,
+                    pattern: "/".to_string(),
+                    name: Some("home".to_string()),
+                    middleware: 1,
+                },
+                RouteEntry {
+                    method: Method::GET,
+                    pattern: "/users/:id".to_string(),
+                    name: Some("user.show".to_string()),
+                    middleware: 2,
+                },
+                RouteEntry {
+                    method: Method::POST,
+                    pattern: "/users/:id".to_string(),
+                    name: Some("user.show".to_string()),
+                    middleware: 1,
+                },
+            ]
+        );
+
+        let tree: Tree = router.clone().into();
+        assert_eq!(tree.routes().cloned().collect::<Vec<_>>(), entries);
+        assert_eq!(tree.table(), &router.table());
+
+        assert_eq!(
+            router.table().to_string(),
+            "\
+METHOD  PATTERN     NAME       MIDDLEWARE
+GET     /           home       1
+GET     /users/:id  user.show  2
+POST    /users/:id  user.show  1
+"
+        );
+    }
+
     fn client(method: Method, path: &str) -> (Request, Method, String) {
         (
             Request::builder()
@@ -0,0 +1,113 @@
+//! Route table
+
+use std::fmt;
+
+use viz_core::Method;
+
+/// A registered route.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// HTTP verb
+    pub method: Method,
+    /// Route pattern
+    pub pattern: String,
+    /// Route name
+    pub name: Option<String>,
+    /// The number of middleware wrapping the handler
+    pub middleware: usize,
+}
+
+/// A list of the registered routes, displayed as a table.
+///
+/// ```text
+/// METHOD  PATTERN         NAME       MIDDLEWARE
+/// GET     /               home       1
+/// GET     /users/:id      user.show  2
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteTable(Vec<RouteEntry>);
+
+impl RouteTable {
+    /// Returns an iterator over the routes.
+    pub fn iter(&self) -> std::slice::Iter<'_, RouteEntry> {
+        self.0.iter()
+    }
+
+    /// Consumes the table, returning the routes.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<RouteEntry> {
+        self.0
+    }
+}
+
+impl FromIterator<RouteEntry> for RouteTable {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = RouteEntry>,
+    {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for RouteTable {
+    type Item = RouteEntry;
+
+    type IntoIter = std::vec::IntoIter<RouteEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a RouteTable {
+    type Item = &'a RouteEntry;
+
+    type IntoIter = std::slice::Iter<'a, RouteEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const HEADER: [&str; 4] = ["METHOD", "PATTERN", "NAME", "MIDDLEWARE"];
+
+        let rows = self
+            .0
+            .iter()
+            .map(|e| {
+                [
+                    e.method.to_string(),
+                    e.pattern.clone(),
+                    e.name.clone().unwrap_or_default(),
+                    e.middleware.to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let widths = rows.iter().fold(HEADER.map(str::len), |mut widths, row| {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+            widths
+        });
+
+        let mut write_row = |row: [&str; 4]| {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())
+        };
+
+        write_row(HEADER)?;
+        for row in &rows {
+            write_row(row.each_ref().map(String::as_str))?;
+        }
+
+        Ok(())
+    }
+}
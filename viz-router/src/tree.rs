@@ -1,14 +1,33 @@
-use std::fmt::{Debug, Formatter, Result};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result},
+};
 
 use path_tree::{Path, PathTree};
 
-use viz_core::{BoxHandler, Method};
+use viz_core::{
+    BoxHandler, Handler, HandlerExt, Method, Request,
+    header::ALLOW,
+    headers::{Allow, HeaderMapExt},
+    types::UrlFor,
+};
 
-use crate::{Route, Router};
+use crate::{
+    Conflict, Conflicts, Normalize, Normalized, RouteEntry, RouteTable, Router,
+    host::HostPattern,
+    normalize::{canonicalize, decode, toggle_trailing_slash},
+};
 
 /// Store all final routes.
 #[derive(Clone, Default)]
-pub struct Tree(Vec<(Method, PathTree<BoxHandler>)>);
+pub struct Tree {
+    routes: Vec<(Method, PathTree<BoxHandler>)>,
+    fallbacks: PathTree<BoxHandler>,
+    normalize: Normalize,
+    urls: UrlFor,
+    table: RouteTable,
+    hosts: Vec<(HostPattern, Tree)>,
+}
 
 impl Tree {
     /// Find a handler by the HTTP method and the URI's path.
@@ -18,54 +37,344 @@ impl Tree {
         method: &'b Method,
         path: &'b str,
     ) -> Option<(&'a BoxHandler, Path<'a, 'b>)> {
-        self.0
+        self.routes
             .iter()
             .find_map(|(m, t)| if m == method { t.find(path) } else { None })
     }
 
+    /// Selects the tree of the first host router matching the host, and the captured params.
+    ///
+    /// Returns the tree itself and no params if no host routers match.
+    #[must_use]
+    pub fn select<'a, 'b>(&'a self, host: Option<&'b str>) -> (&'a Self, Vec<(&'a str, &'b str)>) {
+        host.and_then(|host| {
+            self.hosts
+                .iter()
+                .find_map(|(pattern, tree)| pattern.matches(host).map(|params| (tree, params)))
+        })
+        .unwrap_or((self, Vec::new()))
+    }
+
+    /// Find a fallback handler by the URI's path.
+    ///
+    /// The fallback of the most specific nested router is returned.
+    #[must_use]
+    pub fn fallback<'a, 'b>(&'a self, path: &'b str) -> Option<(&'a BoxHandler, Path<'a, 'b>)> {
+        self.fallbacks.find(path)
+    }
+
+    /// Returns the HTTP verbs which have a route matching the path.
+    ///
+    /// The `HEAD` verb is included implicitly when the path can be handled by `GET`.
+    #[must_use]
+    pub fn allowed(&self, path: &str) -> Vec<Method> {
+        let mut methods = self
+            .routes
+            .iter()
+            .filter_map(|(m, t)| t.find(path).map(|_| m.clone()))
+            .collect::<Vec<_>>();
+
+        if methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+            methods.push(Method::HEAD);
+        }
+
+        methods
+    }
+
+    /// Returns `true` if any verb has a route matching the path.
+    fn matches(&self, path: &str) -> bool {
+        self.routes.iter().any(|(_, t)| t.find(path).is_some())
+    }
+
+    /// Normalizes the path by the policy of the tree.
+    ///
+    /// The path is percent-decoded before matching under every policy, so the params are always
+    /// decoded. Returns `None` if the path is matched as is or the canonical path has no routes
+    /// either.
+    #[must_use]
+    pub fn normalize(&self, path: &str) -> Option<Normalized> {
+        let decoded = decode(path);
+        if self.normalize == Normalize::Strict || self.matches(&decoded) {
+            return (decoded != path).then(|| Normalized::Rewrite(decoded.into_owned()));
+        }
+
+        let canonical = canonicalize(path);
+        let toggled = toggle_trailing_slash(&canonical);
+
+        [Some(canonical), toggled]
+            .into_iter()
+            .flatten()
+            .find_map(|canonical| {
+                let decoded = decode(&canonical);
+                if !self.matches(&decoded) {
+                    None
+                } else if self.normalize == Normalize::Redirect && canonical != path {
+                    Some(Normalized::Redirect(canonical))
+                } else {
+                    Some(Normalized::Rewrite(decoded.into_owned()))
+                }
+            })
+    }
+
+    /// Returns the URL generator of the named routes, including the ones of the host routers.
+    #[must_use]
+    pub const fn urls(&self) -> &UrlFor {
+        &self.urls
+    }
+
+    /// Returns an iterator over the registered routes.
+    pub fn routes(&self) -> std::slice::Iter<'_, RouteEntry> {
+        self.table.iter()
+    }
+
+    /// Returns the registered routes as a table.
+    #[must_use]
+    pub const fn table(&self) -> &RouteTable {
+        &self.table
+    }
+
     /// Consumes the Tree, returning the wrapped value.
     #[must_use]
     pub fn into_inner(self) -> Vec<(Method, PathTree<BoxHandler>)> {
-        self.0
+        self.routes
     }
 }
 
 impl AsRef<Vec<(Method, PathTree<BoxHandler>)>> for Tree {
     fn as_ref(&self) -> &Vec<(Method, PathTree<BoxHandler>)> {
-        &self.0
+        &self.routes
     }
 }
 
 impl AsMut<Vec<(Method, PathTree<BoxHandler>)>> for Tree {
     fn as_mut(&mut self) -> &mut Vec<(Method, PathTree<BoxHandler>)> {
-        &mut self.0
+        &mut self.routes
     }
 }
 
-impl From<Router> for Tree {
-    fn from(router: Router) -> Self {
-        let mut tree = Self::default();
+impl Tree {
+    /// Builds the tree from the router, the duplicate and ambiguous routes and the duplicate
+    /// names are reported whether the router is strict or not.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Conflicts`] if any routes conflict.
+    pub fn build(router: Router) -> std::result::Result<Self, Conflicts> {
+        let (tree, conflicts) = Self::new(router);
+        if conflicts.is_empty() {
+            Ok(tree)
+        } else {
+            Err(Conflicts(conflicts))
+        }
+    }
+
+    /// Builds the tree from the router as [`From`] does, the conflicts are only reported if
+    /// the router is [strict](Router::strict).
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Conflicts`] if the router is strict and any routes conflict.
+    pub fn try_build(router: Router) -> std::result::Result<Self, Conflicts> {
+        let strict = router.strict;
+        let (tree, conflicts) = Self::new(router);
+        if strict && !conflicts.is_empty() {
+            Err(Conflicts(conflicts))
+        } else {
+            Ok(tree)
+        }
+    }
+
+    fn new(router: Router) -> (Self, Vec<Conflict>) {
+        let mut conflicts = router
+            .duplicates
+            .into_iter()
+            .map(|(method, path)| Conflict::Duplicate {
+                method,
+                pattern: format!("/{path}"),
+            })
+            .collect::<Vec<_>>();
+        let mut tree = Self {
+            normalize: router.normalize,
+            ..Self::default()
+        };
+        for (mut path, handler) in router.fallbacks {
+            if !path.is_empty() {
+                path.insert(0, '/');
+                let _ = tree.fallbacks.insert(&path, handler.clone());
+            }
+            path.push_str("/*");
+            let _ = tree.fallbacks.insert(&path, handler);
+        }
+        let mut names = Vec::new();
+        let mut entries = Vec::new();
+        let mut ids = Vec::<(Method, usize, String)>::new();
+        let mut hosts = Vec::new();
+        for (host, router) in router.hosts {
+            let (mut sub, sub_conflicts) = Self::new(router);
+            sub.normalize = tree.normalize;
+            conflicts.extend(sub_conflicts.into_iter().map(|conflict| match conflict {
+                Conflict::Duplicate { method, pattern } => Conflict::Duplicate {
+                    method,
+                    pattern: format!("{host}{pattern}"),
+                },
+                Conflict::Ambiguous {
+                    method,
+                    pattern,
+                    other,
+                } => Conflict::Ambiguous {
+                    method,
+                    pattern: format!("{host}{pattern}"),
+                    other: format!("{host}{other}"),
+                },
+                Conflict::DuplicateName {
+                    name,
+                    pattern,
+                    other,
+                } => Conflict::DuplicateName {
+                    name,
+                    pattern: format!("{host}{pattern}"),
+                    other: format!("{host}{other}"),
+                },
+            }));
+            hosts.extend(sub.routes().map(|entry| RouteEntry {
+                pattern: format!("{host}{}", entry.pattern),
+                ..entry.clone()
+            }));
+            // The names of the root routes are added later, taking precedence.
+            names.extend(sub.routes().filter_map(|entry| {
+                entry
+                    .name
+                    .clone()
+                    .map(|name| (name, entry.pattern.clone(), Some(host.clone())))
+            }));
+            tree.hosts.push((HostPattern::new(&host), sub));
+        }
         if let Some(routes) = router.routes {
-            for (mut path, Route { methods }) in routes {
+            // The explicit `OPTIONS` handlers are inserted later, taking precedence.
+            let synthesized = routes.iter().filter_map(|(path, route)| {
+                let handler = route.auto_options.clone()?;
+                if route.methods.iter().any(|(m, _)| *m == Method::OPTIONS) {
+                    return None;
+                }
+                Some((
+                    format!("/{}", path.trim_start_matches('/')),
+                    with_allow(handler, &route.methods),
+                ))
+            });
+            for (path, handler) in synthesized {
+                if let Some((_, t)) = tree
+                    .as_mut()
+                    .iter_mut()
+                    .find(|(m, _)| *m == Method::OPTIONS)
+                {
+                    let _ = t.insert(&path, handler);
+                } else {
+                    let mut t = PathTree::new();
+                    let _ = t.insert(&path, handler);
+                    tree.as_mut().push((Method::OPTIONS, t));
+                }
+            }
+            for (mut path, route) in routes {
                 if !path.starts_with('/') {
                     path.insert(0, '/');
                 }
-                for (method, handler) in methods {
-                    if let Some(t) = tree
+                entries.extend(route.methods().map(|(method, middleware)| RouteEntry {
+                    method: method.clone(),
+                    pattern: path.clone(),
+                    name: route.name.clone(),
+                    middleware,
+                }));
+                if let Some(name) = route.name.clone() {
+                    names.push((name, path.clone(), None));
+                }
+                for (method, handler) in route {
+                    let id = if let Some(t) = tree
                         .as_mut()
                         .iter_mut()
                         .find_map(|(m, t)| if *m == method { Some(t) } else { None })
                     {
-                        let _ = t.insert(&path, handler);
+                        t.insert(&path, handler)
                     } else {
                         let mut t = PathTree::new();
-                        let _ = t.insert(&path, handler);
-                        tree.as_mut().push((method, t));
+                        let id = t.insert(&path, handler);
+                        tree.as_mut().push((method.clone(), t));
+                        id
+                    };
+                    match ids.iter_mut().find(|(m, i, _)| *m == method && *i == id) {
+                        Some((_, _, other)) => conflicts.push(Conflict::Ambiguous {
+                            method,
+                            pattern: path.clone(),
+                            other: std::mem::replace(other, path.clone()),
+                        }),
+                        None => ids.push((method, id, path.clone())),
                     }
                 }
             }
         }
-        tree
+        let mut patterns = HashMap::<&str, (&str, Option<&str>)>::new();
+        for (name, pattern, host) in &names {
+            let Some((other, other_host)) = patterns.insert(name, (pattern, host.as_deref()))
+            else {
+                continue;
+            };
+            // The duplicates in a host router are reported by itself.
+            if (other, other_host) != (pattern, host.as_deref())
+                && (host.is_none() || other_host != host.as_deref())
+            {
+                conflicts.push(Conflict::DuplicateName {
+                    name: name.clone(),
+                    pattern: format!("{}{pattern}", host.as_deref().unwrap_or_default()),
+                    other: format!("{}{other}", other_host.unwrap_or_default()),
+                });
+            }
+        }
+        // The named routes of all hosts are shared, e.g. linking to a route of another host.
+        tree.urls = UrlFor::new(names.into_iter().map(|(name, pattern, _)| (name, pattern)));
+        for (_, sub) in &mut tree.hosts {
+            sub.urls = tree.urls.clone();
+        }
+        tree.table = entries.into_iter().chain(hosts).collect();
+        (tree, conflicts)
+    }
+}
+
+/// Adds the `Allow` header listing the verbs of the route to the responses of the synthesized
+/// `OPTIONS` handler, unless a middleware has set it.
+fn with_allow(handler: BoxHandler, methods: &[(Method, BoxHandler)]) -> BoxHandler {
+    let mut allowed = Vec::new();
+    for (m, _) in methods {
+        if !allowed.contains(m) {
+            allowed.push(m.clone());
+        }
+    }
+    if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+        allowed.push(Method::HEAD);
+    }
+    allowed.push(Method::OPTIONS);
+    let allow = allowed.into_iter().collect::<Allow>();
+
+    (move |req: Request| {
+        let handler = handler.clone();
+        let allow = allow.clone();
+        async move {
+            let mut res = handler.call(req).await?;
+            if !res.headers().contains_key(ALLOW) {
+                res.headers_mut().typed_insert(allow);
+            }
+            Ok(res)
+        }
+    })
+    .boxed()
+}
+
+impl From<Router> for Tree {
+    /// Builds the tree from the router.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the router is [strict](Router::strict) and any routes conflict.
+    fn from(router: Router) -> Self {
+        Self::try_build(router).unwrap_or_else(|conflicts| panic!("{conflicts}"))
     }
 }
 
@@ -40,7 +40,9 @@
 //!     println!("listening on {}", listener.local_addr().unwrap());
 //!
 //!     // Run it
-//!     viz_smol::serve(ex.clone(), listener, app).await
+//!     viz_smol::serve(ex.clone(), listener, app).await?;
+//!
+//!     Ok(())
 //! }
 //! ```
 //!
@@ -61,10 +63,10 @@ mod listener;
 pub use listener::Listener;
 
 mod server;
-pub use server::serve;
+pub use server::{Server, ShutdownSummary, serve};
 
-// #[cfg(any(feature = "native-tls", feature = "rustls"))]
-// pub use server::tls;
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+pub use server::tls;
 
 pub use viz_core::*;
 pub use viz_router::*;
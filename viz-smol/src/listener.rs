@@ -17,4 +17,13 @@ pub trait Listener {
     /// An error will return if got the socket address of the local half of this connection is
     /// failed.
     fn local_addr(&self) -> std::io::Result<Self::Addr>;
+
+    /// Returns the TLS information of the connection, which is seen by the handlers as
+    /// [`tls_info`](crate::RequestExt::tls_info).
+    ///
+    /// Returns `None` by default, the TLS listeners override it.
+    fn tls_info(io: &Self::Io) -> Option<crate::types::TlsInfo> {
+        let _ = io;
+        None
+    }
 }
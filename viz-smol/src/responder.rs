@@ -1,12 +1,23 @@
 use std::{convert::Infallible, future::Future, pin::Pin, sync::Arc};
 
-use crate::{Body, Handler, Incoming, IntoResponse, Method, Request, Response, StatusCode, Tree};
+use crate::{
+    Body, Handler, Incoming, IntoResponse, Method, Normalized, Request, Response, ResponseExt,
+    StatusCode, Tree,
+    header::HOST,
+    headers::{Allow, HeaderMapExt},
+    timeout::Watch,
+    types::TlsInfo,
+};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
 /// Handles the HTTP [`Request`] and retures the HTTP [`Response`].
 #[derive(Debug)]
 pub struct Responder<A> {
     tree: Arc<Tree>,
     remote_addr: Option<A>,
+    tls_info: Option<TlsInfo>,
+    watch: Option<Arc<Watch>>,
 }
 
 impl<A> Responder<A>
@@ -16,7 +27,24 @@ where
     /// Creates a Responder for handling the [`Request`].
     #[must_use]
     pub fn new(tree: Arc<Tree>, remote_addr: Option<A>) -> Self {
-        Self { tree, remote_addr }
+        Self {
+            tree,
+            remote_addr,
+            tls_info: None,
+            watch: None,
+        }
+    }
+
+    /// Sets the TLS information of the connection.
+    pub(crate) fn tls_info(mut self, tls_info: Option<TlsInfo>) -> Self {
+        self.tls_info = tls_info;
+        self
+    }
+
+    /// Applies the timeouts of the connection to the requests.
+    pub(crate) fn watch(mut self, watch: Arc<Watch>) -> Self {
+        self.watch = Some(watch);
+        self
     }
 }
 
@@ -26,37 +54,104 @@ where
 {
     type Response = Response;
     type Error = Infallible;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: Request<Incoming>) -> Self::Future {
+        let res = self.respond(req);
 
-    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+        match self.watch.clone() {
+            Some(watch) => Box::pin(async move { Ok(watch.request(res).await) }),
+            None => Box::pin(async move { Ok(res.await) }),
+        }
+    }
+}
+
+impl<A> Responder<A>
+where
+    A: Clone + Send + Sync + 'static,
+{
+    /// Routes the request and calls the handler.
+    fn respond(&self, mut req: Request<Incoming>) -> BoxFuture<Response> {
         let method = req.method().clone();
-        let path = req.uri().path().to_owned();
+        let mut path = req.uri().path().to_owned();
+        let host = req
+            .uri()
+            .host()
+            .or_else(|| req.headers().get(HOST).and_then(|v| v.to_str().ok()))
+            .map(ToOwned::to_owned);
+        let (tree, host_params) = self.tree.select(host.as_deref());
+
+        match tree.normalize(&path) {
+            Some(Normalized::Redirect(mut location)) => {
+                if let Some(query) = req.uri().query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+                return Box::pin(async move { Response::permanent(location) });
+            }
+            Some(Normalized::Rewrite(rewritten)) => path = rewritten,
+            None => {}
+        }
 
-        let Some((handler, route)) = self.tree.find(&method, &path).or_else(|| {
+        let found = tree.find(&method, &path).or_else(|| {
             if method == Method::HEAD {
-                self.tree.find(&Method::GET, &path)
+                tree.find(&Method::GET, &path)
+            } else {
+                None
+            }
+        });
+        let allowed = if found.is_none() {
+            tree.allowed(&path)
+        } else {
+            Vec::new()
+        };
+        // The fallback only handles the paths matching no verbs.
+        let Some((handler, route)) = found.or_else(|| {
+            if allowed.is_empty() {
+                tree.fallback(&path)
             } else {
                 None
             }
         }) else {
-            return Box::pin(async move { Ok(StatusCode::NOT_FOUND.into_response()) });
+            return Box::pin(async move { not_matched(allowed) });
         };
 
         req.extensions_mut().insert(self.remote_addr.clone());
+        if let Some(tls_info) = self.tls_info.clone() {
+            req.extensions_mut().insert(tls_info);
+        }
+        req.extensions_mut().insert(tree.urls().clone());
         req.extensions_mut()
             .insert(Arc::from(crate::types::RouteInfo {
                 id: *route.id,
                 pattern: route.pattern(),
-                params: route.params().into(),
+                params: host_params
+                    .into_iter()
+                    .chain(route.params())
+                    .collect::<Vec<_>>()
+                    .into(),
             }));
 
         let handler = handler.clone();
 
         Box::pin(async move {
-            Ok(handler
+            handler
                 .call(req.map(Body::Incoming))
                 .await
-                .unwrap_or_else(IntoResponse::into_response))
+                .unwrap_or_else(IntoResponse::into_response)
         })
     }
 }
+
+/// Responds `404 Not Found` if no route matches the path, otherwise `405 Method Not Allowed`
+/// with the `Allow` header listing the verbs registered for the path.
+fn not_matched(allowed: Vec<Method>) -> Response {
+    if allowed.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let mut res = StatusCode::METHOD_NOT_ALLOWED.into_response();
+    res.headers_mut()
+        .typed_insert(allowed.into_iter().collect::<Allow>());
+    res
+}
@@ -1,13 +1,28 @@
-use std::{borrow::Borrow, fmt::Debug, io, sync::Arc};
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug},
+    future::{Future, IntoFuture, Pending, pending},
+    io,
+    marker::PhantomData,
+    pin::{Pin, pin},
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
 
 use async_executor::Executor;
-use futures_lite::io::{AsyncRead, AsyncWrite};
+use futures_lite::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+};
 use hyper::rt::Timer;
 #[cfg(any(feature = "http1", feature = "http2"))]
 use hyper_util::server::conn::auto::Builder;
-use smol_hyper::rt::{FuturesIo, SmolExecutor, SmolTimer};
+use smol_hyper::rt::{FuturesIo, SmolTimer};
 
-use crate::{Listener, Responder, Router, Tree};
+use crate::{
+    Conflicts, Listener, Responder, Router, Tree,
+    timeout::{Timeouts, Watch, Watched},
+};
 
 #[cfg(any(feature = "http1", feature = "http2"))]
 mod tcp;
@@ -15,60 +30,324 @@ mod tcp;
 #[cfg(all(unix, feature = "unix-socket"))]
 mod unix;
 
-// TLS
-// #[cfg(any(feature = "native-tls", feature = "rustls"))]
-// pub mod tls;
+mod shutdown;
+pub use shutdown::ShutdownSummary;
+use shutdown::{Connections, Hook};
+
+/// TLS
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+pub mod tls;
 
 /// Serve a server with smol's networking types.
-#[allow(clippy::missing_errors_doc)]
-pub async fn serve<'ex, E, L>(executor: E, listener: L, router: Router) -> io::Result<()>
+///
+/// The [`Server`] is configured by its methods and runs once awaited, resolving to a
+/// [`ShutdownSummary`]. It is spawned on the executor with
+/// [`into_future`](IntoFuture::into_future):
+///
+/// ```no_run
+/// # use std::{future::IntoFuture, sync::Arc};
+/// # async fn run(ex: Arc<async_executor::Executor<'static>>) -> std::io::Result<()> {
+/// use async_net::TcpListener;
+/// use viz_smol::{Router, serve};
+///
+/// let listener = TcpListener::bind("127.0.0.1:3000").await?;
+/// let server = ex.spawn(serve(ex.clone(), listener, Router::new()).into_future());
+/// let summary = server.await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// If the router is [strict](Router::strict) and any routes conflict, the server fails to run
+/// with an [`InvalidInput`](io::ErrorKind::InvalidInput) error.
+pub fn serve<'ex, E, L>(executor: E, listener: L, router: Router) -> Server<'ex, E, L> {
+    let (tree, conflicts) = match Tree::try_build(router) {
+        Ok(tree) => (tree, None),
+        Err(conflicts) => (Tree::default(), Some(conflicts)),
+    };
+    Server {
+        executor,
+        listener,
+        signal: pending(),
+        tree: Arc::new(tree),
+        options: Options {
+            conflicts,
+            ..Options::default()
+        },
+        _executor: PhantomData,
+    }
+}
+
+/// A listening HTTP server that accepts connections, awaits it to run.
+pub struct Server<'ex, E, L, S = Pending<()>> {
+    executor: E,
+    listener: L,
+    signal: S,
+    tree: Arc<Tree>,
+    options: Options,
+    _executor: PhantomData<&'ex ()>,
+}
+
+/// The options of the server.
+#[derive(Debug)]
+struct Options {
+    shutdown_timeout: Duration,
+    on_shutdown: Vec<Hook>,
+    on_shutdown_complete: Vec<Hook>,
+    timeouts: Timeouts,
+    /// The conflicts of the strict router, which fail the server.
+    conflicts: Option<Conflicts>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            shutdown_timeout: Duration::from_secs(10),
+            on_shutdown: Vec::new(),
+            on_shutdown_complete: Vec::new(),
+            timeouts: Timeouts::default(),
+            conflicts: None,
+        }
+    }
+}
+
+impl<E, L, S> Debug for Server<'_, E, L, S>
+where
+    L: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("listener", &self.listener)
+            .field("tree", &self.tree)
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'ex, E, L> Server<'ex, E, L> {
+    /// Specifies a signal for graceful shutdown.
+    pub fn signal<S>(self, signal: S) -> Server<'ex, E, L, S> {
+        Server {
+            signal,
+            executor: self.executor,
+            listener: self.listener,
+            tree: self.tree,
+            options: self.options,
+            _executor: PhantomData,
+        }
+    }
+}
+
+impl<E, L, S> Server<'_, E, L, S> {
+    /// Specifies how long to wait for the connections to be drained on shutdown,
+    /// defaults to 10 seconds.
+    ///
+    /// The connections still open after the timeout are force-closed.
+    #[must_use]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.options.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Adds a hook fired when the signal is received, before draining the connections.
+    #[must_use]
+    pub fn on_shutdown<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.options.on_shutdown.push(Hook::new(f));
+        self
+    }
+
+    /// Adds a hook fired when the connections are drained or force-closed.
+    #[must_use]
+    pub fn on_shutdown_complete<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.options.on_shutdown_complete.push(Hook::new(f));
+        self
+    }
+
+    /// Specifies how long to wait for the header of a request on HTTP/1 connections, including
+    /// the next request on a kept-alive connection.
+    ///
+    /// `408 Request Timeout` is responded if a part of the header is read in time, otherwise the
+    /// connection is closed. The idle HTTP/2 connections are closed by the
+    /// [idle timeout](Self::idle_timeout).
+    #[must_use]
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.header_read = Some(timeout);
+        self
+    }
+
+    /// Specifies how long a connection can be idle between the requests before it is closed.
+    ///
+    /// The connection is not idle until the responses, including their bodies, are finished.
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.idle = Some(timeout);
+        self
+    }
+
+    /// Specifies how long a handler can take to produce the response, otherwise it is
+    /// cancelled and `503 Service Unavailable` is responded.
+    #[must_use]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.request = Some(timeout);
+        self
+    }
+}
+
+impl<'ex, E, L, S> IntoFuture for Server<'ex, E, L, S>
 where
     E: Borrow<Executor<'ex>> + Clone + Send + 'ex,
     L: Listener + Send + 'static,
     L::Io: AsyncRead + AsyncWrite + Send + Unpin,
     L::Addr: Send + Sync + Debug,
+    S: Future + Send + 'ex,
 {
-    let tree = Arc::<Tree>::new(router.into());
-
-    loop {
-        // Wait for a new connection.
-        let (stream, remote_addr) = match listener.accept().await {
-            Ok(conn) => conn,
-            Err(e) => {
-                if !is_connection_error(&e) {
-                    // [From `hyper::Server` in 0.14](https://github.com/hyperium/hyper/blob/v0.14.27/src/server/tcp.rs#L186)
-                    tracing::error!("listener accept error: {e}");
-                    SmolTimer::new()
-                        .sleep(std::time::Duration::from_secs(1))
-                        .await;
-                }
-                continue;
+    type Output = io::Result<ShutdownSummary<L::Addr>>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'ex>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let Self {
+            executor,
+            listener,
+            signal,
+            tree,
+            options:
+                Options {
+                    shutdown_timeout,
+                    on_shutdown,
+                    on_shutdown_complete,
+                    timeouts,
+                    conflicts,
+                },
+            ..
+        } = self;
+
+        Box::pin(async move {
+            if let Some(conflicts) = conflicts {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, conflicts));
             }
-        };
-
-        // Wrap it in a `FuturesIo`.
-        let io = FuturesIo::new(stream);
-        let remote_addr = Arc::new(remote_addr);
-        let responder = Responder::<Arc<L::Addr>>::new(tree.clone(), Some(remote_addr.clone()));
-
-        // Spawn the service on our executor.
-        let task = executor.borrow().spawn({
-            let executor = executor.clone();
-            async move {
-                let mut builder = Builder::new(SmolExecutor::new(AsRefExecutor(executor.borrow())));
-                #[cfg(feature = "http1")]
-                builder.http1().timer(SmolTimer::new());
-                #[cfg(feature = "http2")]
-                builder.http2().timer(SmolTimer::new());
-
-                if let Err(err) = builder.serve_connection_with_upgrades(io, responder).await {
-                    tracing::error!("unintelligible hyper error: {err}");
-                }
+
+            // Closed to shut down the connections gracefully.
+            let (shutdown, shutting_down) = async_channel::bounded::<()>(1);
+            // Closed once all the connections are drained.
+            let (drain, drained) = async_channel::bounded::<()>(1);
+            let conns = Arc::new(Connections::new());
+            let mut signal = pin!(signal);
+
+            loop {
+                // Wait for a new connection or the signal, only the `Send` future of the
+                // listener is held across the await.
+                let accept = listener.accept();
+                let accept = async { Some(accept.await) };
+                let signaled = async {
+                    signal.as_mut().await;
+                    None
+                };
+                let Some(conn) = future::or(accept, signaled).await else {
+                    drop(listener);
+                    tracing::trace!("Signal received, starting shutdown");
+                    break;
+                };
+
+                let (stream, remote_addr) = match conn {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        if !is_connection_error(&e) {
+                            // [From `hyper::Server` in 0.14](https://github.com/hyperium/hyper/blob/v0.14.27/src/server/tcp.rs#L186)
+                            tracing::error!("listener accept error: {e}");
+                            SmolTimer::new().sleep(Duration::from_secs(1)).await;
+                        }
+                        continue;
+                    }
+                };
+
+                let tls_info = L::tls_info(&stream);
+
+                let watch = Watch::new(timeouts, SmolTimer::new());
+
+                // Wrap it in a `FuturesIo`.
+                let io = Watched::new(FuturesIo::new(stream), watch.clone());
+                let remote_addr = Arc::new(remote_addr);
+                let responder =
+                    Responder::<Arc<L::Addr>>::new(tree.clone(), Some(remote_addr.clone()))
+                        .tls_info(tls_info)
+                        .watch(watch);
+
+                let shutting_down = shutting_down.clone();
+                let drain = drain.clone();
+
+                // Spawn the service on our executor.
+                conns.spawn(executor.borrow(), remote_addr, {
+                    let executor = executor.clone();
+                    async move {
+                        let mut builder = Builder::new(SharedExecutor::new(executor));
+                        #[cfg(feature = "http1")]
+                        {
+                            let mut http1 = builder.http1();
+                            http1.timer(SmolTimer::new());
+                            // The header timeout of the watched IO responds 408 instead.
+                            if timeouts.header_read.is_some() {
+                                http1.header_read_timeout(None);
+                            }
+                        }
+                        #[cfg(feature = "http2")]
+                        builder.http2().timer(SmolTimer::new());
+
+                        let mut conn = pin!(builder.serve_connection_with_upgrades(io, responder));
+                        let serving = async { Some(conn.as_mut().await) };
+                        let shutdown = async {
+                            let _ = shutting_down.recv().await;
+                            None
+                        };
+                        let res = if let Some(res) = future::or(serving, shutdown).await {
+                            res
+                        } else {
+                            conn.as_mut().graceful_shutdown();
+                            conn.await
+                        };
+
+                        if let Err(err) = res {
+                            tracing::error!("unintelligible hyper error: {err}");
+                        }
+                        drop(drain);
+                    }
+                });
             }
-        });
 
-        // Detach the task and let it run forever.
-        task.detach();
+            on_shutdown.into_iter().for_each(Hook::call);
+
+            // Keep-alive connections are sent `Connection: close` or HTTP/2 `GOAWAY`.
+            let open = conns.len();
+            shutdown.close();
+            drop(drain);
+            let drained = async {
+                let _ = drained.recv().await;
+                tracing::trace!("Gracefully shutdown!");
+                Vec::new()
+            };
+            let timeout = async {
+                SmolTimer::new().sleep(shutdown_timeout).await;
+                let forced = conns.cancel_all();
+                tracing::error!(
+                    "Waited {:?} for graceful shutdown, force-closed {} connections",
+                    shutdown_timeout,
+                    forced.len()
+                );
+                forced
+            };
+            let forced = future::or(drained, timeout).await;
+
+            on_shutdown_complete.into_iter().for_each(Hook::call);
+
+            Ok(ShutdownSummary {
+                drained: open.saturating_sub(forced.len()),
+                forced,
+            })
+        })
     }
 }
 
@@ -81,12 +360,31 @@ fn is_connection_error(e: &io::Error) -> bool {
     )
 }
 
-#[derive(Clone)]
-struct AsRefExecutor<'this, 'ex>(&'this Executor<'ex>);
+/// Spawns the tasks of hyper on the executor.
+///
+/// The HTTP/2 connections share it between their streams, so it is `Sync` whatever `E` is.
+struct SharedExecutor<E>(Arc<Mutex<E>>);
 
-impl<'ex> AsRef<Executor<'ex>> for AsRefExecutor<'_, 'ex> {
-    #[inline]
-    fn as_ref(&self) -> &Executor<'ex> {
-        self.0
+impl<E> SharedExecutor<E> {
+    fn new(executor: E) -> Self {
+        Self(Arc::new(Mutex::new(executor)))
+    }
+}
+
+impl<E> Clone for SharedExecutor<E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<'ex, E, F> hyper::rt::Executor<F> for SharedExecutor<E>
+where
+    E: Borrow<Executor<'ex>>,
+    F: Future + Send + 'ex,
+    F::Output: Send + 'ex,
+{
+    fn execute(&self, fut: F) {
+        let executor = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        (*executor).borrow().spawn(fut).detach();
     }
 }
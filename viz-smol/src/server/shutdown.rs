@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_executor::{Executor, Task};
+
+/// The summary of a graceful shutdown.
+#[derive(Debug)]
+pub struct ShutdownSummary<A> {
+    /// The number of the connections which were drained before the timeout.
+    pub drained: usize,
+    /// The peer addresses of the connections which were force-closed after the timeout.
+    pub forced: Vec<Arc<A>>,
+}
+
+/// A callback fired on shutdown.
+pub(crate) struct Hook(Box<dyn FnOnce() + Send>);
+
+impl Hook {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Self(Box::new(f))
+    }
+
+    pub(crate) fn call(self) {
+        (self.0)();
+    }
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+/// The open connections, which are cancelled if they are not drained in time.
+#[derive(Debug)]
+pub(crate) struct Connections<A> {
+    next: AtomicU64,
+    open: Mutex<HashMap<u64, (Arc<A>, Task<()>)>>,
+}
+
+impl<A> Connections<A>
+where
+    A: Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spawns a task for serving the connection, which is removed once finished.
+    pub(crate) fn spawn<'ex, F>(
+        self: &Arc<Self>,
+        executor: &Executor<'ex>,
+        peer_addr: Arc<A>,
+        conn: F,
+    ) where
+        F: Future<Output = ()> + Send + 'ex,
+    {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        // Holds the lock until the task is registered, the task can not remove itself before.
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let conns = Arc::downgrade(self);
+        let task = executor.spawn(async move {
+            conn.await;
+            if let Some(conns) = conns.upgrade() {
+                let finished = conns
+                    .open
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&id);
+                // Dropping the task would cancel it.
+                if let Some((_, task)) = finished {
+                    task.detach();
+                }
+            }
+        });
+
+        open.insert(id, (peer_addr, task));
+    }
+
+    /// Returns the number of the open connections.
+    pub(crate) fn len(&self) -> usize {
+        self.open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Cancels the open connections, returns their peer addresses.
+    pub(crate) fn cancel_all(&self) -> Vec<Arc<A>> {
+        let open = self
+            .open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain()
+            .map(|(_, conn)| conn)
+            .collect::<Vec<_>>();
+        // Dropping the tasks cancels them, after the lock is released.
+        open.into_iter().map(|(peer_addr, _)| peer_addr).collect()
+    }
+}
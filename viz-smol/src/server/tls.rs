@@ -1,21 +1,92 @@
 //! A TLS listener wrapper.
 
+use std::{
+    fmt,
+    future::{Future, poll_fn},
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    pin::Pin,
+    sync::{Mutex, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_io::Timer;
+
+use futures_lite::future;
+use futures_util::{
+    future::Either,
+    stream::{FuturesUnordered, StreamExt},
+};
+
+use crate::{Listener, types::TlsInfo};
+
+/// `native_tls`
+#[cfg(feature = "native-tls")]
+pub mod native_tls;
+
+/// `rustls`
+#[cfg(feature = "rustls")]
+pub mod rustls;
+
+/// A TLS acceptor, which performs the handshakes of the accepted connections.
+pub trait Acceptor<IO> {
+    /// The stream's type after the handshake.
+    type Stream;
+
+    /// Performs the handshake of the connection.
+    fn accept(&self, io: IO) -> impl Future<Output = IoResult<Self::Stream>> + Send + 'static;
+
+    /// Returns the TLS information of the established stream.
+    fn tls_info(stream: &Self::Stream) -> TlsInfo;
+}
+
+type Handshake<S, Addr> = Pin<Box<dyn Future<Output = (IoResult<S>, Addr)> + Send>>;
+
 /// Unified TLS listener type.
-#[derive(Debug)]
-pub struct TlsListener<T, A> {
+pub struct TlsListener<T, A>
+where
+    T: Listener,
+    A: Acceptor<T::Io>,
+{
     pub(crate) inner: T,
     pub(crate) acceptor: A,
+    handshake_timeout: Duration,
+    max_handshakes: usize,
+    handshakes: Mutex<FuturesUnordered<Handshake<A::Stream, T::Addr>>>,
 }
 
-impl<T, A> TlsListener<T, A> {
+impl<T, A> TlsListener<T, A>
+where
+    T: Listener,
+    A: Acceptor<T::Io>,
+{
     /// Creates a new TLS listener.
     pub fn new(t: T, a: A) -> Self {
         Self {
             inner: t,
             acceptor: a,
+            handshake_timeout: Duration::from_secs(10),
+            max_handshakes: 1024,
+            handshakes: Mutex::new(FuturesUnordered::new()),
         }
     }
 
+    /// Specifies how long a handshake can take, defaults to 10 seconds, the stalled clients are
+    /// closed.
+    #[must_use]
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Limits the number of the pending handshakes, defaults to 1024, accepting new
+    /// connections is paused until a pending one finishes.
+    #[must_use]
+    pub fn max_handshakes(mut self, max: usize) -> Self {
+        self.max_handshakes = max.max(1);
+        self
+    }
+
     /// Gets the listener.
     pub fn get_ref(&self) -> &T {
         &self.inner
@@ -25,4 +96,94 @@ impl<T, A> TlsListener<T, A> {
     pub fn get_acceptor(&self) -> &A {
         &self.acceptor
     }
+
+    fn pending_handshakes(&self) -> usize {
+        self.handshakes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    fn poll_handshakes(&self, cx: &mut Context<'_>) -> Poll<(IoResult<A::Stream>, T::Addr)> {
+        match self
+            .handshakes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .poll_next_unpin(cx)
+        {
+            Poll::Ready(Some(handshake)) => Poll::Ready(handshake),
+            // Polled again once a new connection is accepted.
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T, A> fmt::Debug for TlsListener<T, A>
+where
+    T: Listener + fmt::Debug,
+    A: Acceptor<T::Io> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsListener")
+            .field("inner", &self.inner)
+            .field("acceptor", &self.acceptor)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("max_handshakes", &self.max_handshakes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, A> Listener for TlsListener<T, A>
+where
+    T: Listener + Sync,
+    T::Addr: Send + 'static,
+    A: Acceptor<T::Io> + Sync,
+    A::Stream: Send + 'static,
+{
+    type Io = A::Stream;
+    type Addr = T::Addr;
+
+    /// Runs the handshakes concurrently while accepting the connections, a stalled client does
+    /// not delay the others.
+    ///
+    /// The failed and timed out handshakes are dropped, the pending ones are kept for the next
+    /// call. Accepting is paused while the pending handshakes reach the limit.
+    async fn accept(&self) -> IoResult<(Self::Io, Self::Addr)> {
+        loop {
+            let full = self.pending_handshakes() >= self.max_handshakes;
+            let handshaked = async { Either::Left(poll_fn(|cx| self.poll_handshakes(cx)).await) };
+            let accepted = async {
+                if full {
+                    future::pending::<()>().await;
+                }
+                Either::Right(self.inner.accept().await)
+            };
+
+            match future::or(handshaked, accepted).await {
+                Either::Left((Ok(stream), addr)) => return Ok((stream, addr)),
+                Either::Left((Err(e), _)) => tracing::debug!("tls handshake error: {e}"),
+                Either::Right(accepted) => {
+                    let (io, addr) = accepted?;
+                    let handshake = self.acceptor.accept(io);
+                    let timeout = self.handshake_timeout;
+                    let handshake = future::or(handshake, async move {
+                        Timer::after(timeout).await;
+                        Err(IoError::new(ErrorKind::TimedOut, "handshake timed out"))
+                    });
+                    self.handshakes
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(Box::pin(async move { (handshake.await, addr) }));
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> IoResult<Self::Addr> {
+        self.inner.local_addr()
+    }
+
+    fn tls_info(io: &Self::Io) -> Option<TlsInfo> {
+        Some(A::tls_info(io))
+    }
 }
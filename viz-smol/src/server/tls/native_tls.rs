@@ -0,0 +1,65 @@
+use std::{fmt, io::Result as IoResult};
+
+use futures_lite::{AsyncRead, AsyncWrite};
+use tokio_native_tls::native_tls::TlsAcceptor as TlsAcceptorWrapper;
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+
+use crate::{Error, Result, types::TlsInfo};
+
+pub use tokio_native_tls::{TlsAcceptor, native_tls::Identity};
+
+/// A TLS stream, which is adapted from and to the `futures` IO traits.
+pub type TlsStream<IO> = Compat<tokio_native_tls::TlsStream<Compat<IO>>>;
+
+/// [`native-tls`]'s config.
+pub struct Config {
+    identity: Identity,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeTls Config").finish()
+    }
+}
+
+impl Config {
+    /// Creates a new config with the specified [`Identity`].
+    #[must_use]
+    pub fn new(identity: Identity) -> Self {
+        Self { identity }
+    }
+
+    /// Creates a new [`TlsAcceptor`] wrapper with the specified [`Identity`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if wrapping the identity fails.
+    pub fn build(self) -> Result<TlsAcceptor> {
+        TlsAcceptorWrapper::new(self.identity)
+            .map(Into::into)
+            .map_err(Error::boxed)
+    }
+}
+
+impl<IO> crate::tls::Acceptor<IO> for TlsAcceptor
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<IO>;
+
+    fn accept(&self, io: IO) -> impl Future<Output = IoResult<Self::Stream>> + Send + 'static {
+        let acceptor = self.clone();
+        async move {
+            acceptor
+                .accept(io.compat())
+                .await
+                .map(TokioAsyncReadCompatExt::compat)
+                .map_err(std::io::Error::other)
+        }
+    }
+
+    /// The SNI and ALPN are not exposed by `native-tls`.
+    fn tls_info(_: &Self::Stream) -> TlsInfo {
+        TlsInfo::default()
+    }
+}
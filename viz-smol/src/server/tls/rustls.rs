@@ -0,0 +1,24 @@
+use std::io::Result as IoResult;
+
+use futures_lite::{AsyncRead, AsyncWrite};
+use futures_rustls::server::TlsStream;
+
+use crate::types::TlsInfo;
+
+pub use futures_rustls::TlsAcceptor;
+pub use viz_core::tls::rustls::Config;
+
+impl<IO> crate::tls::Acceptor<IO> for TlsAcceptor
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<IO>;
+
+    fn accept(&self, io: IO) -> impl Future<Output = IoResult<Self::Stream>> + Send + 'static {
+        self.accept(io)
+    }
+
+    fn tls_info(stream: &Self::Stream) -> TlsInfo {
+        viz_core::tls::rustls::tls_info(stream.get_ref().1)
+    }
+}
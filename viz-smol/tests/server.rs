@@ -0,0 +1,173 @@
+//! Server test cases
+
+use std::{
+    future::IntoFuture,
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_io::Timer;
+use async_net::{TcpListener, TcpStream};
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use macro_rules_attribute::apply;
+use smol_macros::{Executor, test};
+use viz_smol::{Request, Router, serve};
+
+fn slow(delay: Duration) -> Router {
+    Router::new().get("/slow", move |_: Request| async move {
+        Timer::after(delay).await;
+        Ok("slow")
+    })
+}
+
+async fn get_slow(addr: std::net::SocketAddr) -> io::Result<String> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await?;
+    Ok(resp)
+}
+
+#[apply(test!)]
+async fn graceful_shutdown(ex: &Arc<Executor<'_>>) -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let completed = Arc::new(AtomicBool::new(false));
+    let server = ex.spawn(
+        serve(ex.clone(), listener, slow(Duration::from_millis(300)))
+            .signal(Timer::after(Duration::from_millis(100)))
+            .on_shutdown_complete({
+                let completed = completed.clone();
+                move || completed.store(true, Ordering::SeqCst)
+            })
+            .into_future(),
+    );
+
+    // The in-flight request is finished, then the connection is closed.
+    let resp = get_slow(addr).await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK"));
+    assert!(resp.ends_with("slow"));
+
+    let summary = server.await?;
+    assert_eq!(summary.drained, 1);
+    assert!(summary.forced.is_empty());
+    assert!(completed.load(Ordering::SeqCst));
+
+    // The listener is closed.
+    assert!(TcpStream::connect(addr).await.is_err());
+
+    Ok(())
+}
+
+#[apply(test!)]
+async fn shutdown_timeout(ex: &Arc<Executor<'_>>) -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = ex.spawn(
+        serve(ex.clone(), listener, slow(Duration::from_secs(10)))
+            .signal(Timer::after(Duration::from_millis(100)))
+            .shutdown_timeout(Duration::from_millis(100))
+            .into_future(),
+    );
+
+    // The connection is force-closed without a response.
+    assert_eq!(get_slow(addr).await?, "");
+
+    let summary = server.await?;
+    assert_eq!(summary.drained, 0);
+    assert_eq!(summary.forced.len(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "rustls")]
+#[apply(test!)]
+async fn tls(ex: &Arc<Executor<'_>>) -> io::Result<()> {
+    use futures_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, pki_types::ServerName},
+    };
+    use viz_smol::{RequestExt, tls};
+
+    const CERT: &[u8] = include_bytes!("../../viz-test/tests/tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("../../viz-test/tests/tls/key.pem");
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let config = tls::rustls::Config::new()
+        .cert(CERT)
+        .key(KEY)
+        .build()
+        .map_err(io::Error::other)?;
+    let listener =
+        tls::TlsListener::new(listener, tls::rustls::TlsAcceptor::from(Arc::new(config)))
+            .handshake_timeout(Duration::from_millis(200));
+    let router = Router::new().get("/", |req: Request| async move {
+        Ok(req
+            .tls_info()
+            .and_then(|info| info.server_name.clone())
+            .unwrap_or_default())
+    });
+    let server = ex.spawn(serve(ex.clone(), listener, router).into_future());
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &CERT[..]) {
+        roots.add(cert?).map_err(io::Error::other)?;
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    // A client stalled in the handshake does not delay the others.
+    let mut stalled = TcpStream::connect(addr).await?;
+
+    let request = async {
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(
+                ServerName::try_from("localhost").map_err(io::Error::other)?,
+                stream,
+            )
+            .await?;
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).await?;
+        io::Result::Ok(resp)
+    };
+    let timeout = async {
+        Timer::after(Duration::from_secs(10)).await;
+        Err(io::ErrorKind::TimedOut.into())
+    };
+    let resp = futures_lite::future::or(request, timeout).await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK"));
+    assert!(resp.ends_with("localhost"));
+
+    // The stalled client is closed once its handshake times out.
+    let closed = async {
+        let mut buf = [0; 1];
+        io::Result::Ok(stalled.read(&mut buf).await.unwrap_or(0))
+    };
+    let timeout = async {
+        Timer::after(Duration::from_secs(10)).await;
+        Err(io::ErrorKind::TimedOut.into())
+    };
+    assert_eq!(futures_lite::future::or(closed, timeout).await?, 0);
+
+    drop(server.cancel().await);
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use reqwest::Client;
 use std::{future::IntoFuture, net::SocketAddr};
 use tokio::net::TcpListener;
-use viz::{Error, Result, Router, serve};
+use viz::{Error, Result, Router, ServerHandle, serve};
 
 pub use http;
 pub use nano_id;
@@ -12,6 +12,7 @@ pub use reqwest::{RequestBuilder, multipart};
 pub struct TestServer {
     addr: SocketAddr,
     client: Client,
+    handle: ServerHandle,
 }
 
 impl TestServer {
@@ -28,9 +29,16 @@ impl TestServer {
             .build()
             .map_err(Error::boxed)?;
 
-        tokio::spawn(serve(listener, router).into_future());
+        let server = serve(listener, router);
+        let handle = server.handle();
 
-        Ok(Self { addr, client })
+        tokio::spawn(server.into_future());
+
+        Ok(Self {
+            addr,
+            client,
+            handle,
+        })
     }
 
     fn path(&self, url: impl AsRef<str>) -> String {
@@ -42,6 +50,11 @@ impl TestServer {
         self.addr
     }
 
+    #[must_use]
+    pub fn handle(&self) -> &ServerHandle {
+        &self.handle
+    }
+
     pub fn get(&self, url: impl AsRef<str>) -> RequestBuilder {
         self.client.get(self.path(url))
     }
@@ -57,4 +70,9 @@ impl TestServer {
     pub fn put(&self, url: impl AsRef<str>) -> RequestBuilder {
         self.client.put(self.path(url))
     }
+
+    pub fn options(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.client
+            .request(reqwest::Method::OPTIONS, self.path(url))
+    }
 }
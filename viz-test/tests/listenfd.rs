@@ -0,0 +1,68 @@
+//! The inherited listeners, in their own test binary since the environment is modified.
+
+#![cfg(unix)]
+
+use std::{
+    env,
+    future::IntoFuture,
+    os::fd::{IntoRawFd, OwnedFd},
+    process,
+};
+
+use viz::{Error, ListenFds, Request, Result, Router, serve};
+
+#[test]
+fn listen_fds() -> Result<()> {
+    let inherited = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = inherited.local_addr()?;
+    let fd = inherited.into_raw_fd();
+
+    // SAFETY: this is the only test of the binary and no threads are spawned yet.
+    unsafe {
+        env::set_var("LISTEN_PID", process::id().to_string());
+        env::set_var("LISTEN_FDS", "1");
+        env::set_var("LISTEN_FDS_FIRST_FD", fd.to_string());
+        env::set_var("LISTEN_FDNAMES", "http");
+    }
+
+    let mut fds = ListenFds::from_env();
+    assert_eq!(fds.len(), 1);
+    assert_eq!(fds.position("http"), Some(0));
+    assert_eq!(fds.position("https"), None);
+    assert!(env::var("LISTEN_FDS").is_err());
+
+    tokio::runtime::Runtime::new()?.block_on(async move {
+        let router = Router::new().get("/", |_: Request| async { Ok("inherited") });
+
+        let listener = fds.take_tcp(0)?.expect("the inherited listener");
+        assert!(fds.take_tcp(0)?.is_none());
+        assert!(fds.take_tcp(1)?.is_none());
+
+        let server = tokio::spawn(serve(listener, router.clone()).into_future());
+        let resp = reqwest::get(format!("http://{addr}/"))
+            .await
+            .map_err(Error::boxed)?;
+        assert_eq!(resp.text().await.map_err(Error::boxed)?, "inherited");
+        server.abort();
+
+        // A pre-bound socket.
+        let bound = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = bound.local_addr()?;
+        let listener = ListenFds::tcp_from_fd(bound.into())?;
+        assert_eq!(listener.local_addr()?, addr);
+
+        let server = tokio::spawn(serve(listener, router).into_future());
+        let resp = reqwest::get(format!("http://{addr}/"))
+            .await
+            .map_err(Error::boxed)?;
+        assert_eq!(resp.text().await.map_err(Error::boxed)?, "inherited");
+        server.abort();
+
+        // A UDP socket is not a listener.
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        let err = ListenFds::tcp_from_fd(OwnedFd::from(udp)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        Ok(())
+    })
+}
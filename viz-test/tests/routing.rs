@@ -0,0 +1,410 @@
+use headers::{Allow, HeaderMapExt};
+use viz::{Error, Method, Request, Result, Router, StatusCode};
+
+#[tokio::test]
+async fn method_not_allowed() -> Result<()> {
+    use viz_test::TestServer;
+
+    let router = Router::new()
+        .get("/users", |_: Request| async { Ok("list") })
+        .post("/users", |_: Request| async { Ok("create") })
+        .delete("/users/:id", |_: Request| async { Ok("delete") });
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.put("/users").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let allow = resp.headers().typed_get::<Allow>().unwrap();
+    assert_eq!(
+        allow.iter().collect::<Vec<_>>(),
+        vec![Method::GET, Method::POST, Method::HEAD]
+    );
+
+    let resp = client.get("/users/1").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let allow = resp.headers().typed_get::<Allow>().unwrap();
+    assert_eq!(allow.iter().collect::<Vec<_>>(), vec![Method::DELETE]);
+
+    let resp = client.get("/posts").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(resp.headers().typed_get::<Allow>().is_none());
+
+    let resp = client.get("/users").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn auto_options() -> Result<()> {
+    use viz::middleware::cors;
+    use viz_test::TestServer;
+
+    let router = Router::new()
+        .auto_options()
+        .get("/users", |_: Request| async { Ok("list") })
+        .delete("/users/:id", |_: Request| async { Ok("delete") })
+        .options("/users/:id", |_: Request| async { Ok("explicit") })
+        .merge(Router::new().post("/users", |_: Request| async { Ok("create") }))
+        .with(cors::Config::new().allow_origins(["https://viz.rs"]))
+        .strict();
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client
+        .options("/users")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let allow = resp.headers().typed_get::<Allow>().unwrap();
+    assert_eq!(
+        allow.iter().collect::<Vec<_>>(),
+        vec![Method::GET, Method::POST, Method::HEAD, Method::OPTIONS]
+    );
+
+    // The preflight requests are answered by the middleware wrapping the synthesized handler.
+    let resp = client
+        .options("/users")
+        .header("origin", "https://viz.rs")
+        .header("access-control-request-method", "POST")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert!(resp.status().is_success());
+    assert!(resp.headers().contains_key("access-control-allow-methods"));
+
+    let resp = client
+        .options("/users/1")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "explicit");
+
+    let resp = client
+        .options("/posts")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn fallback() -> Result<()> {
+    use viz::{Handler, IntoResponse, Next, Response};
+    use viz_test::TestServer;
+
+    async fn powered_by<H>((req, h): Next<Request, H>) -> Result<Response>
+    where
+        H: Handler<Request, Output = Result<Response>>,
+    {
+        let mut resp = h.call(req).await?;
+        resp.headers_mut()
+            .insert("x-powered-by", "viz".parse().map_err(Error::boxed)?);
+        Ok(resp)
+    }
+
+    let api = Router::new()
+        .get("/users", |_: Request| async { Ok("users") })
+        .fallback(|_: Request| async {
+            Ok((StatusCode::NOT_FOUND, "api not found").into_response())
+        });
+
+    let router = Router::new()
+        .get("/", |_: Request| async { Ok("index") })
+        .nest("/api", api)
+        .fallback(|_: Request| async { Ok("spa") })
+        .with_handler(powered_by);
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/about").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("x-powered-by").unwrap(), "viz");
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "spa");
+
+    let resp = client
+        .get("/api/posts")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get("x-powered-by").unwrap(), "viz");
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "api not found");
+
+    let resp = client.post("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn normalize() -> Result<()> {
+    use viz::{Normalize, RequestExt, header::LOCATION};
+    use viz_test::TestServer;
+
+    let router = Router::new()
+        .get("/users", |_: Request| async { Ok("users") })
+        .get("/tags/:name", |req: Request| async move {
+            Ok(req.param::<String>("name")?)
+        });
+
+    let client = TestServer::new(router.clone().normalize(Normalize::Redirect)).await?;
+
+    let resp = client
+        .get("/users/?page=2")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers().get(LOCATION).unwrap(), "/users?page=2");
+
+    let resp = client.get("//users").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers().get(LOCATION).unwrap(), "/users");
+
+    // The params are decoded whether the path is canonical or not.
+    let resp = client
+        .get("/tags/caf%C3%A9/")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.headers().get(LOCATION).unwrap(), "/tags/caf%C3%A9");
+    let resp = client
+        .get("/tags/caf%C3%A9")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "café");
+
+    let client = TestServer::new(router.clone().normalize(Normalize::Match)).await?;
+
+    let resp = client.get("/users/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "users");
+
+    let resp = client
+        .get("/tags/caf%C3%A9/")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "café");
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/users/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // The params are decoded by the strict policy too.
+    let resp = client
+        .get("/tags/caf%C3%A9")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "café");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn url_for() -> Result<()> {
+    use viz::{IntoHandler, RequestExt, get, types::UrlFor};
+    use viz_test::TestServer;
+
+    async fn show(urls: UrlFor) -> Result<String> {
+        Ok(urls.url_for("user.show", [("id", 7)])?)
+    }
+
+    let router = Router::new()
+        .route("/users/:id", get(show.into_handler()).name("user.show"))
+        .get("/", |req: Request| async move {
+            Ok(req.url_for("user.show", [("id", "a b"), ("tab", "posts")])?)
+        })
+        .get("/missing", |req: Request| async move {
+            Ok(req.url_for("user.show", [("tab", "posts")])?)
+        });
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(
+        resp.text().await.map_err(Error::boxed)?,
+        "/users/a%20b?tab=posts"
+    );
+
+    let resp = client.get("/users/1").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "/users/7");
+
+    let resp = client.get("/missing").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        resp.text().await.map_err(Error::boxed)?,
+        "missing `id` param"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn url_for_host() -> Result<()> {
+    use viz::{RequestExt, header::HOST};
+    use viz_test::TestServer;
+
+    let api = Router::new()
+        .route(
+            "/posts/:id",
+            viz::get(|_: Request| async { Ok(()) }).name("api.post"),
+        )
+        .get("/", |req: Request| async move {
+            Ok(req.url_for("user.show", [("id", 1)])?)
+        });
+    let router = Router::new()
+        .route(
+            "/users/:id",
+            viz::get(|_: Request| async { Ok(()) }).name("user.show"),
+        )
+        .get("/", |req: Request| async move {
+            Ok(req.url_for("api.post", [("id", 2)])?)
+        })
+        .host("api.example.com", api);
+
+    let client = TestServer::new(router).await?;
+
+    // The names are shared by the root and host routers.
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "/posts/2");
+    let resp = client
+        .get("/")
+        .header(HOST, "api.example.com")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "/users/1");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn host() -> Result<()> {
+    use viz::{RequestExt, header::HOST};
+    use viz_test::TestServer;
+
+    let api = Router::new().get("/users", |_: Request| async { Ok("api users") });
+    let tenant = Router::new().get("/users/:id", |req: Request| async move {
+        let (tenant, id) = req.params::<(String, u32)>()?;
+        Ok(format!("{tenant} {id}"))
+    });
+
+    let router = Router::new()
+        .get("/users", |_: Request| async { Ok("users") })
+        .host("api.example.com", api)
+        .host("{tenant}.example.com", tenant);
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/users").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "users");
+
+    let resp = client
+        .get("/users")
+        .header(HOST, "api.example.com:8080")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "api users");
+
+    let resp = client
+        .get("/users/7")
+        .header(HOST, "acme.example.com")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "acme 7");
+
+    let resp = client
+        .get("/users/7")
+        .header(HOST, "api.example.com")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn guard() -> Result<()> {
+    use viz::{Guard, Handler, IntoResponse, Next, Response, get, header::ACCEPT};
+    use viz_test::TestServer;
+
+    async fn tagged<H>((req, h): Next<Request, H>) -> Result<Response>
+    where
+        H: Handler<Request, Output = Result<Response>>,
+    {
+        let mut resp = h
+            .call(req)
+            .await
+            .unwrap_or_else(IntoResponse::into_response);
+        resp.headers_mut()
+            .insert("x-route", "posts".parse().map_err(Error::boxed)?);
+        Ok(resp)
+    }
+
+    let router = Router::new()
+        .route(
+            "/users",
+            get(|_: Request| async { Ok("v2") }).guard(Guard::accept("application/vnd.x.v2+json")),
+        )
+        .get("/users", |_: Request| async { Ok("v1") })
+        .route(
+            "/posts",
+            get(|_: Request| async { Ok("posts") })
+                .guard(Guard::accept("application/json"))
+                .with_handler(tagged),
+        );
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/users").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "v1");
+
+    let resp = client
+        .get("/users")
+        .header(ACCEPT, "application/vnd.x.v2+json")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "v2");
+
+    let resp = client
+        .get("/posts")
+        .header(ACCEPT, "text/html")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+    // The rejection goes through the middleware of the route.
+    assert_eq!(resp.headers()["x-route"], "posts");
+
+    let resp = client
+        .get("/posts")
+        .header(ACCEPT, "application/json;q=0, text/html")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+
+    let resp = client
+        .get("/posts")
+        .header(ACCEPT, "text/html, application/json; q=0.5")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "posts");
+
+    Ok(())
+}
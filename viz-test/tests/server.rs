@@ -0,0 +1,1229 @@
+use std::{
+    env,
+    future::IntoFuture,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use viz::{
+    BoxError, Bytes, Either, Error, ProxyProtocol, Request, RequestExt, Result, Router,
+    ServerStats, StatusCode, http3,
+    middleware::hsts,
+    serve,
+    tls::{
+        self, HttpsRedirect,
+        rustls::{self, ReloadableAcceptor},
+    },
+};
+use viz_test::TestServer;
+
+#[cfg(unix)]
+use viz::{ListenFds, Upgrade};
+
+#[tokio::test]
+async fn swap_router() -> Result<()> {
+    let router =
+        Router::new()
+            .get("/", |_: Request| async { Ok("v1") })
+            .get("/slow", |_: Request| async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok("slow v1")
+            });
+
+    let client = TestServer::new(router).await?;
+
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "v1");
+
+    let in_flight = tokio::spawn(client.get("/slow").send());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    client
+        .handle()
+        .swap_router(Router::new().get("/", |_: Request| async { Ok("v2") }))
+        .map_err(Error::boxed)?;
+
+    let resp = in_flight
+        .await
+        .map_err(Error::boxed)?
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "slow v1");
+
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "v2");
+
+    let resp = client.get("/slow").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // The conflicting routes of a strict router are rejected, the current ones are kept.
+    let conflicting = Router::new()
+        .get("/", |_: Request| async { Ok("v3") })
+        .merge(Router::new().get("/", |_: Request| async { Ok("v3") }))
+        .strict();
+    assert!(client.handle().swap_router(conflicting.clone()).is_err());
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "v2");
+
+    // The server fails to run with them.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let err = serve(listener, conflicting).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn graceful_shutdown() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let router = Router::new()
+        .get("/slow", |_: Request| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok("slow")
+        })
+        .get("/stuck", |_: Request| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok("stuck")
+        });
+
+    let server = serve(listener, router)
+        .signal(async {
+            rx.await.ok();
+        })
+        .shutdown_timeout(Duration::from_millis(500))
+        .on_shutdown({
+            let events = events.clone();
+            move || events.lock().unwrap().push("shutdown")
+        })
+        .on_shutdown_complete({
+            let events = events.clone();
+            move || events.lock().unwrap().push("complete")
+        });
+    let server = tokio::spawn(server.into_future());
+
+    let client = reqwest::Client::new();
+    let slow = tokio::spawn(client.get(format!("http://{addr}/slow")).send());
+    let stuck = tokio::spawn(client.get(format!("http://{addr}/stuck")).send());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    tx.send(()).ok();
+
+    let resp = slow.await.map_err(Error::boxed)?.map_err(Error::boxed)?;
+    assert_eq!(resp.headers()[http::header::CONNECTION], "close");
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "slow");
+
+    let summary = server.await.map_err(Error::boxed)??;
+    assert_eq!(summary.drained, 1);
+    assert_eq!(summary.forced.len(), 1);
+    assert!(stuck.await.map_err(Error::boxed)?.is_err());
+
+    assert_eq!(*events.lock().unwrap(), ["shutdown", "complete"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn handle() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+
+    let router = Router::new().get("/slow", |_: Request| async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        Ok("slow")
+    });
+
+    let server = serve(listener, router);
+    let handle = server.handle();
+    let addr = *handle.local_addr().unwrap();
+    let server = tokio::spawn(server.into_future());
+
+    assert_eq!(handle.stats(), ServerStats::default());
+
+    let client = reqwest::Client::new();
+    let slow = tokio::spawn(client.get(format!("http://{addr}/slow")).send());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(
+        handle.stats(),
+        ServerStats {
+            open_connections: 1,
+            accepted: 1,
+            rejected: 0,
+            in_flight: 1,
+        }
+    );
+
+    let resp = slow.await.map_err(Error::boxed)?.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "slow");
+    assert_eq!(handle.stats().in_flight, 0);
+
+    assert!(!handle.is_shutdown());
+    handle.shutdown();
+    assert!(handle.is_shutdown());
+
+    let summary = server.await.map_err(Error::boxed)??;
+    assert!(summary.forced.is_empty());
+    assert!(TcpListener::bind(addr).await.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_connections() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+
+    let server = serve(listener, Router::new()).max_connections(1);
+    let handle = server.handle();
+    let addr = *handle.local_addr().unwrap();
+    let server = tokio::spawn(server.into_future());
+
+    let first = TcpStream::connect(addr).await?;
+    let _second = TcpStream::connect(addr).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(handle.stats().accepted, 1);
+
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(handle.stats().accepted, 2);
+    assert_eq!(handle.stats().open_connections, 1);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_connections_per_ip() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+
+    let server = serve(listener, Router::new()).max_connections_per_ip(1);
+    let handle = server.handle();
+    let addr = *handle.local_addr().unwrap();
+    let server = tokio::spawn(server.into_future());
+
+    let _first = TcpStream::connect(addr).await?;
+    let mut second = TcpStream::connect(addr).await?;
+    let mut buf = [0; 1];
+    assert_eq!(second.read(&mut buf).await?, 0);
+
+    let stats = handle.stats();
+    assert_eq!(stats.accepted, 2);
+    assert_eq!(stats.rejected, 1);
+    assert_eq!(stats.open_connections, 1);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn header_read_timeout() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = serve(
+        listener,
+        Router::new().get("/", |_: Request| async { Ok("") }),
+    )
+    .header_read_timeout(Duration::from_millis(500));
+    let handle = server.handle();
+    let server = tokio::spawn(server.into_future());
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: viz.rs\r\n")
+        .await?;
+
+    // The partial header is responded with 408.
+    let mut buf = String::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_string(&mut buf))
+        .await
+        .map_err(Error::boxed)??;
+    assert!(buf.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+
+    // The kept-alive connection is closed without a response once the next header is late.
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: viz.rs\r\n\r\n")
+        .await?;
+    let mut buf = String::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_string(&mut buf))
+        .await
+        .map_err(Error::boxed)??;
+    assert!(buf.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(!buf.contains("408"));
+
+    // The HTTP/2 connections are not limited by the header timeout.
+    let client = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .map_err(Error::boxed)?;
+    let resp = client
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.version(), http::Version::HTTP_2);
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    let resp = client
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(handle.stats().accepted, 3);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn idle_timeout() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = serve(
+        listener,
+        Router::new().get("/", |_: Request| async { Ok("idle") }),
+    )
+    .idle_timeout(Duration::from_millis(500));
+    let handle = server.handle();
+    let server = tokio::spawn(server.into_future());
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: viz.rs\r\n\r\n")
+        .await?;
+
+    let mut buf = String::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_string(&mut buf))
+        .await
+        .map_err(Error::boxed)??;
+    assert!(buf.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(buf.ends_with("idle"));
+
+    // The connection is untracked once its task finishes.
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while handle.stats().open_connections > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .map_err(Error::boxed)?;
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_timeout() -> Result<()> {
+    let router = Router::new()
+        .get("/", |_: Request| async { Ok("fast") })
+        .get("/slow", |_: Request| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok("slow")
+        });
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = serve(listener, router).request_timeout(Duration::from_millis(500));
+    let server = tokio::spawn(server.into_future());
+
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "fast");
+
+    let resp = client
+        .get(format!("http://{addr}/slow"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn multiple_listeners() -> Result<()> {
+    let http = TcpListener::bind("127.0.0.1:0").await?;
+    let http_addr = http.local_addr()?;
+    let admin = TcpListener::bind("127.0.0.1:0").await?;
+    let admin_addr = admin.local_addr()?;
+
+    let router = Router::new().get("/", |req: Request| async move {
+        Ok(req
+            .remote_addr()
+            .map(ToString::to_string)
+            .unwrap_or_default())
+    });
+
+    let server = serve((http, admin), router);
+    let handle = server.handle();
+    assert!(matches!(handle.local_addr(), Some(Either::Left(addr)) if *addr == http_addr));
+    let server = tokio::spawn(server.into_future());
+
+    let client = reqwest::Client::new();
+    for addr in [http_addr, admin_addr] {
+        let resp = client
+            .get(format!("http://{addr}/"))
+            .send()
+            .await
+            .map_err(Error::boxed)?;
+        let remote_addr = resp.text().await.map_err(Error::boxed)?;
+        assert!(remote_addr.starts_with("127.0.0.1:"));
+    }
+    assert_eq!(handle.stats().accepted, 2);
+
+    handle.shutdown();
+    let summary = server.await.map_err(Error::boxed)??;
+    assert!(summary.forced.is_empty());
+    assert!(TcpListener::bind(http_addr).await.is_ok());
+    assert!(TcpListener::bind(admin_addr).await.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn multiple_listeners_pending_handshake() -> Result<()> {
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, pki_types::ServerName},
+    };
+
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+
+    let https = TcpListener::bind("127.0.0.1:0").await?;
+    let https_addr = https.local_addr()?;
+    let http = TcpListener::bind("127.0.0.1:0").await?;
+    let http_addr = http.local_addr()?;
+
+    let acceptor =
+        rustls::TlsAcceptor::from(Arc::new(rustls::Config::new().cert(CERT).key(KEY).build()?));
+    let router = Router::new().get("/", |_: Request| async { Ok("ok") });
+    let server = serve((tls::TlsListener::new(https, acceptor), http), router);
+    let server = tokio::spawn(server.into_future());
+
+    // The handshake is pending while the other listener accepts.
+    let pending = TcpStream::connect(https_addr).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let resp = reqwest::get(format!("http://{http_addr}/"))
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "ok");
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &*CERT) {
+        roots.add(cert?).map_err(Error::boxed)?;
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::boxed)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(
+            ServerName::try_from("localhost").map_err(Error::boxed)?,
+            pending,
+        )
+        .await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(resp.ends_with("ok"));
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_handshake_limits() -> Result<()> {
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, pki_types::ServerName},
+    };
+
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let acceptor =
+        rustls::TlsAcceptor::from(Arc::new(rustls::Config::new().cert(CERT).key(KEY).build()?));
+    let listener = tls::TlsListener::new(listener, acceptor)
+        .handshake_timeout(Duration::from_millis(200))
+        .max_handshakes(1);
+    let router = Router::new().get("/", |_: Request| async { Ok("ok") });
+    let server = tokio::spawn(serve(listener, router).into_future());
+
+    // The stalled client takes the only handshake slot until it times out.
+    let started = tokio::time::Instant::now();
+    let mut stalled = TcpStream::connect(addr).await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let pending = TcpStream::connect(addr).await?;
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &*CERT) {
+        roots.add(cert?).map_err(Error::boxed)?;
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::boxed)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let mut stream = tokio::time::timeout(
+        Duration::from_secs(10),
+        TlsConnector::from(Arc::new(config)).connect(
+            ServerName::try_from("localhost").map_err(Error::boxed)?,
+            pending,
+        ),
+    )
+    .await
+    .map_err(Error::boxed)??;
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    let mut buf = [0; 1];
+    let read = tokio::time::timeout(Duration::from_secs(10), stalled.read(&mut buf))
+        .await
+        .map_err(Error::boxed)?;
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn https_redirect() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let redirect = HttpsRedirect::new().port(8443).map_port(addr.port(), 9443);
+    let server = tokio::spawn(serve(listener, redirect.into()).into_future());
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(Error::boxed)?;
+
+    let resp = client
+        .post(format!("http://{addr}/users?page=2"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        resp.headers()[http::header::LOCATION],
+        "https://127.0.0.1:9443/users?page=2"
+    );
+
+    let resp = client
+        .get(format!("http://{addr}/"))
+        .header(http::header::HOST, "viz.rs:8080")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(
+        resp.headers()[http::header::LOCATION],
+        "https://viz.rs:8443/"
+    );
+
+    let resp = client
+        .get(format!("http://{addr}/"))
+        .header(http::header::HOST, "viz.rs")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(
+        resp.headers()[http::header::LOCATION],
+        "https://viz.rs:8443/"
+    );
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hsts() -> Result<()> {
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+
+    let router = Router::new()
+        .get("/", |_: Request| async { Ok("secure") })
+        .with(
+            hsts::Config::new()
+                .max_age(Duration::from_secs(63_072_000))
+                .include_sub_domains(true)
+                .preload(true),
+        );
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("https://localhost:{}/", listener.local_addr()?.port());
+    let config = rustls::Config::new().cert(CERT).key(KEY).build()?;
+    let listener = tls::TlsListener::new(listener, rustls::TlsAcceptor::from(Arc::new(config)));
+    let server = tokio::spawn(serve(listener, router.clone()).into_future());
+
+    let resp = reqwest::Client::builder()
+        .tls_built_in_root_certs(false)
+        .add_root_certificate(reqwest::Certificate::from_pem(CERT).map_err(Error::boxed)?)
+        .build()
+        .map_err(Error::boxed)?
+        .get(&url)
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(
+        resp.headers()[http::header::STRICT_TRANSPORT_SECURITY],
+        "max-age=63072000; includeSubDomains; preload"
+    );
+
+    server.abort();
+
+    // Not sent over plain HTTP.
+    let client = TestServer::new(router).await?;
+    let resp = client.get("/").send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        !resp
+            .headers()
+            .contains_key(http::header::STRICT_TRANSPORT_SECURITY)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_reload() -> Result<()> {
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+    const CERT2: &[u8] = include_bytes!("tls/cert2.pem");
+
+    fn client(cert: &[u8]) -> Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_pem(cert).map_err(Error::boxed)?)
+            .build()
+            .map_err(Error::boxed)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("https://localhost:{}/", listener.local_addr()?.port());
+
+    let acceptor = ReloadableAcceptor::new(rustls::Config::new().cert(CERT).key(KEY))?;
+    let listener = tls::TlsListener::new(listener, acceptor.clone());
+    let router = Router::new().get("/", |_: Request| async { Ok("tls") });
+    let server = tokio::spawn(serve(listener, router).into_future());
+
+    let resp = client(CERT)?.get(&url).send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "tls");
+    assert!(client(CERT2)?.get(&url).send().await.is_err());
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls");
+    acceptor.reload_files(format!("{dir}/cert2.pem"), format!("{dir}/key2.pem"))?;
+    assert!(client(CERT2)?.get(&url).send().await.is_ok());
+    assert!(client(CERT)?.get(&url).send().await.is_err());
+
+    // The invalid config is not applied.
+    assert!(acceptor.reload(rustls::Config::new()).is_err());
+    assert!(client(CERT2)?.get(&url).send().await.is_ok());
+
+    acceptor
+        .clone()
+        .reload_stream(futures_util::stream::iter([rustls::Config::new()
+            .cert(CERT)
+            .key(KEY)]))
+        .await;
+    assert!(client(CERT)?.get(&url).send().await.is_ok());
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_sni() -> Result<()> {
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+    // `*.example.test`
+    const CERT3: &[u8] = include_bytes!("tls/cert3.pem");
+    const KEY3: &[u8] = include_bytes!("tls/key3.pem");
+
+    fn client(cert: &[u8], addr: SocketAddr) -> Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_pem(cert).map_err(Error::boxed)?)
+            .resolve("api.example.test", addr)
+            .resolve("other.test", addr)
+            .build()
+            .map_err(Error::boxed)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let port = addr.port();
+
+    let config = rustls::Config::new()
+        .cert(CERT)
+        .key(KEY)
+        .sni("*.Example.test", CERT3, KEY3)
+        .alpn_protocols(["h2", "http/1.1"]);
+    let acceptor = rustls::TlsAcceptor::from(Arc::new(config.build()?));
+    let listener = tls::TlsListener::new(listener, acceptor);
+    let router = Router::new().get("/", |req: Request| async move {
+        let info = req.tls_info().cloned().unwrap_or_default();
+        Ok(format!(
+            "{} {}",
+            info.server_name.unwrap_or_default(),
+            String::from_utf8_lossy(&info.alpn_protocol.unwrap_or_default())
+        ))
+    });
+    let server = tokio::spawn(serve(listener, router).into_future());
+
+    let resp = client(CERT3, addr)?
+        .get(format!("https://api.example.test:{port}/"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(
+        resp.text().await.map_err(Error::boxed)?,
+        "api.example.test h2"
+    );
+
+    // The default certificate is used for the unknown server names.
+    let resp = client(CERT, addr)?
+        .get(format!("https://localhost:{port}/"))
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "localhost h2");
+    assert!(
+        client(CERT3, addr)?
+            .get(format!("https://other.test:{port}/"))
+            .send()
+            .await
+            .is_err()
+    );
+
+    // The key must match the certificate.
+    assert!(
+        rustls::Config::new()
+            .sni("localhost", CERT, KEY3)
+            .build()
+            .is_err()
+    );
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn tls_client_cert() -> Result<()> {
+    use viz::{IntoHandler, get, types::ClientCert};
+
+    const CERT: &[u8] = include_bytes!("tls/cert.pem");
+    const KEY: &[u8] = include_bytes!("tls/key.pem");
+    const CA: &[u8] = include_bytes!("tls/ca.pem");
+    // Signed by the CA, `O=Viz, CN=billing, internal`.
+    const CLIENT: &[u8] = include_bytes!("tls/client.pem");
+    const CLIENT_KEY: &[u8] = include_bytes!("tls/client-key.pem");
+    const CERT3: &[u8] = include_bytes!("tls/cert3.pem");
+    const KEY3: &[u8] = include_bytes!("tls/key3.pem");
+
+    async fn whoami(cert: ClientCert) -> Result<String> {
+        Ok(format!(
+            "{} {} {} {:?}",
+            cert.chain().len(),
+            cert.subject(),
+            cert.common_name().unwrap_or_default(),
+            cert.subject_alt_names()
+        ))
+    }
+
+    fn client(identity: Option<(&[u8], &[u8])>) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_pem(CERT).map_err(Error::boxed)?);
+        if let Some((cert, key)) = identity {
+            builder = builder.identity(
+                reqwest::Identity::from_pem(&[cert, key].concat()).map_err(Error::boxed)?,
+            );
+        }
+        builder.build().map_err(Error::boxed)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("https://localhost:{}/", listener.local_addr()?.port());
+
+    let config = rustls::Config::new()
+        .cert(CERT)
+        .key(KEY)
+        .client_auth_optional(CA);
+    let acceptor = rustls::TlsAcceptor::from(Arc::new(config.build()?));
+    let listener = tls::TlsListener::new(listener, acceptor);
+    let router = Router::new().route("/", get(whoami.into_handler()));
+    let server = tokio::spawn(serve(listener, router).into_future());
+
+    let resp = client(Some((CLIENT, CLIENT_KEY)))?
+        .get(&url)
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.text().await.map_err(Error::boxed)?,
+        r#"1 CN=billing\, internal,O=Viz billing, internal [Dns("billing.internal"), Uri("spiffe://viz/billing"), Ip(10.0.0.1), Email("ops@example.test")]"#
+    );
+
+    // The anonymous clients are allowed, but the certificate is missing.
+    let resp = client(None)?.get(&url).send().await.map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // The certificates not signed by the CA are rejected in the handshake.
+    assert!(client(Some((CERT3, KEY3)))?.get(&url).send().await.is_err());
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn proxy_protocol() -> Result<()> {
+    async fn get(addr: SocketAddr, header: &[u8]) -> Result<String> {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(header).await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: viz.rs\r\nconnection: close\r\n\r\n")
+            .await?;
+
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).await?;
+        Ok(buf)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let router = Router::new().get("/", |req: Request| async move {
+        Ok(req
+            .remote_addr()
+            .map(ToString::to_string)
+            .unwrap_or_default())
+    });
+    let server = tokio::spawn(serve(ProxyProtocol::new(listener), router).into_future());
+
+    let resp = get(addr, b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\n").await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(resp.ends_with("203.0.113.7:56324"));
+
+    let resp = get(addr, b"PROXY TCP6 2001:db8::7 2001:db8::1 56324 443\r\n").await?;
+    assert!(resp.ends_with("[2001:db8::7]:56324"));
+
+    // The address of the balancer is kept.
+    let resp = get(addr, b"PROXY UNKNOWN\r\n").await?;
+    assert!(resp.contains("\r\n\r\n127.0.0.1:"));
+
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x12".to_vec();
+    header.extend([198, 51, 100, 9, 10, 0, 0, 1]);
+    header.extend(8080_u16.to_be_bytes());
+    header.extend(443_u16.to_be_bytes());
+    // A TLV is skipped.
+    header.extend([0x04, 0x00, 0x03, b'a', b'b', b'c']);
+    let resp = get(addr, &header).await?;
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(resp.ends_with("198.51.100.9:8080"));
+
+    // The connection without the header is closed, the next ones are still served.
+    assert_eq!(get(addr, b"").await?, "");
+    let resp = get(addr, b"PROXY TCP4 203.0.113.8 10.0.0.1 56325 443\r\n").await?;
+    assert!(resp.ends_with("203.0.113.8:56325"));
+
+    server.abort();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn proxy_protocol_stalled_client() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let router = Router::new().get("/", |req: Request| async move {
+        Ok(req
+            .remote_addr()
+            .map(ToString::to_string)
+            .unwrap_or_default())
+    });
+    let listener = ProxyProtocol::new(listener).timeout(Duration::from_secs(30));
+    let server = tokio::spawn(serve(listener, router).into_future());
+
+    // The header of the first client never arrives.
+    let _stalled = TcpStream::connect(addr).await?;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\n")
+        .await?;
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: viz.rs\r\nconnection: close\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    tokio::time::timeout(Duration::from_secs(10), stream.read_to_string(&mut resp))
+        .await
+        .map_err(Error::boxed)??;
+    assert!(resp.ends_with("203.0.113.7:56324"));
+
+    server.abort();
+
+    Ok(())
+}
+
+const QUIC_CERT: &[u8] = include_bytes!("tls/cert.pem");
+const QUIC_KEY: &[u8] = include_bytes!("tls/key.pem");
+
+/// A QUIC client endpoint trusting the test certificate.
+fn quic_client() -> std::result::Result<quinn::Endpoint, BoxError> {
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, crypto::aws_lc_rs, version::TLS13};
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &*QUIC_CERT) {
+        roots.add(cert?)?;
+    }
+    let mut config = ClientConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_protocol_versions(&[&TLS13])?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![http3::ALPN.to_vec()];
+
+    let mut endpoint = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+        quinn::crypto::rustls::QuicClientConfig::try_from(config)?,
+    )));
+    Ok(endpoint)
+}
+
+#[tokio::test]
+async fn http3() -> Result<()> {
+    async fn post(addr: SocketAddr, body: &'static str) -> std::result::Result<String, BoxError> {
+        let endpoint = quic_client()?;
+        let conn = endpoint.connect(addr, "localhost")?.await?;
+
+        let (mut driver, mut sender) = h3::client::new(h3_quinn::Connection::new(conn)).await?;
+        let driver =
+            tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+        let req = http::Request::post(format!("https://localhost:{}/", addr.port())).body(())?;
+        let mut stream = sender.send_request(req).await?;
+        stream.send_data(Bytes::from(body)).await?;
+        stream.finish().await?;
+
+        let resp = stream.recv_response().await?;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let mut text = String::new();
+        while let Some(mut chunk) = stream.recv_data().await? {
+            use bytes::Buf;
+            text.push_str(std::str::from_utf8(
+                &chunk.copy_to_bytes(chunk.remaining()),
+            )?);
+        }
+
+        drop(sender);
+        driver.abort();
+        endpoint.close(0u32.into(), b"");
+
+        Ok(text)
+    }
+
+    let router = Router::new().post("/", |mut req: Request| async move {
+        let body = req.text().await?;
+        Ok(format!(
+            "{:?} {} {body}",
+            req.version(),
+            String::from_utf8_lossy(
+                &req.tls_info()
+                    .and_then(|info| info.alpn_protocol.clone())
+                    .unwrap_or_default()
+            ),
+        ))
+    });
+
+    let config = rustls::Config::new()
+        .cert(QUIC_CERT)
+        .key(QUIC_KEY)
+        .build()?;
+    let quic = http3::Server::bind(([127, 0, 0, 1], 0).into(), config, router.clone())?;
+    let handle = quic.handle();
+    let addr = *handle.local_addr().unwrap();
+    let alt_svc = quic.alt_svc()?;
+    assert_eq!(alt_svc, format!("h3=\":{}\"; ma=86400", addr.port()));
+    let server = tokio::spawn(quic.into_future());
+
+    let text = post(addr, "quic").await.map_err(Error::Boxed)?;
+    assert_eq!(text, "HTTP/3.0 h3 quic");
+    assert_eq!(handle.stats().accepted, 1);
+
+    // The TCP server advertises the HTTP/3 server.
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let tcp = listener.local_addr()?;
+    let tcp_server = tokio::spawn(
+        serve(listener, router)
+            .alt_svc(alt_svc.clone())
+            .into_future(),
+    );
+    let resp = reqwest::Client::new()
+        .post(format!("http://{tcp}/"))
+        .body("tcp")
+        .send()
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.headers()[http::header::ALT_SVC], alt_svc);
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "HTTP/1.1  tcp");
+    tcp_server.abort();
+
+    handle.shutdown();
+    let summary = server.await.map_err(Error::boxed)??;
+    assert!(summary.forced.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn http3_limits() -> Result<()> {
+    let config = rustls::Config::new()
+        .cert(QUIC_CERT)
+        .key(QUIC_KEY)
+        .build()?;
+    let router = Router::new().get("/slow", |_: Request| async {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok("slow")
+    });
+    let quic = http3::Server::bind(([127, 0, 0, 1], 0).into(), config, router)?
+        .max_connections_per_ip(1)
+        .idle_timeout(Duration::from_millis(500))
+        .request_timeout(Duration::from_millis(200));
+    let handle = quic.handle();
+    let addr = *handle.local_addr().unwrap();
+    let server = tokio::spawn(quic.into_future());
+
+    let endpoint = quic_client().map_err(Error::Boxed)?;
+    let conn = endpoint
+        .connect(addr, "localhost")
+        .map_err(Error::boxed)?
+        .await
+        .map_err(Error::boxed)?;
+    let (mut driver, mut sender) = h3::client::new(h3_quinn::Connection::new(conn.clone()))
+        .await
+        .map_err(Error::boxed)?;
+    let driver =
+        tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+    // The slow request times out.
+    let req = http::Request::get(format!("https://localhost:{}/slow", addr.port()))
+        .body(())
+        .map_err(Error::boxed)?;
+    let mut stream = sender.send_request(req).await.map_err(Error::boxed)?;
+    stream.finish().await.map_err(Error::boxed)?;
+    let resp = tokio::time::timeout(Duration::from_secs(5), stream.recv_response())
+        .await
+        .map_err(Error::boxed)?
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    drop(stream);
+
+    // The second connection of the same IP is refused.
+    let refused = endpoint
+        .connect(addr, "localhost")
+        .map_err(Error::boxed)?
+        .await;
+    assert!(refused.is_err());
+    assert_eq!(handle.stats().rejected, 1);
+
+    // The idle connection is closed.
+    tokio::time::timeout(Duration::from_secs(10), conn.closed())
+        .await
+        .map_err(Error::boxed)?;
+    driver.abort();
+
+    handle.shutdown();
+    server.await.map_err(Error::boxed)??;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn upgrade() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+
+    // The new process runs `upgrade_child`.
+    let upgrade = Upgrade::program(env::current_exe()?)
+        .args(["upgrade_child", "--exact", "--nocapture"])
+        .env("VIZ_UPGRADE_CHILD", "1")
+        .listener("http", &listener)?;
+
+    let router = Router::new()
+        .get("/", |_: Request| async { Ok("old") })
+        .get("/slow", |_: Request| async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok("slow old")
+        });
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let child = Arc::new(Mutex::new(None));
+    let signal = {
+        let child = child.clone();
+        async move {
+            let _ = rx.await;
+            let spawned = upgrade.spawn().await.expect("the new process is ready");
+            *child.lock().unwrap() = Some(spawned);
+        }
+    };
+    let server = tokio::spawn(serve(listener, router).signal(signal).into_future());
+
+    let resp = reqwest::get(&url).await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "old");
+
+    let in_flight = tokio::spawn(reqwest::get(format!("{url}/slow")));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    tx.send(()).unwrap();
+
+    // The old server stops accepting and drains the in-flight request.
+    let summary = server.await.map_err(Error::boxed)??;
+    assert!(summary.forced.is_empty());
+    let resp = in_flight
+        .await
+        .map_err(Error::boxed)?
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "slow old");
+
+    let resp = reqwest::get(&url).await.map_err(Error::boxed)?;
+    assert_eq!(resp.text().await.map_err(Error::boxed)?, "new");
+
+    let resp = reqwest::get(format!("{url}/quit"))
+        .await
+        .map_err(Error::boxed)?;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let mut child = child.lock().unwrap().take().unwrap();
+    assert!(child.wait()?.success());
+
+    // The new process fails before being ready.
+    let upgrade = Upgrade::program(env::current_exe()?)
+        .args(["upgrade_child", "--exact"])
+        .env("VIZ_UPGRADE_CHILD", "0");
+    let err = upgrade.spawn().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn upgrade_child() -> Result<()> {
+    // Only runs in the new process of `upgrade`.
+    let Some(ready) = env::var_os("VIZ_UPGRADE_CHILD") else {
+        return Ok(());
+    };
+    if ready != "1" {
+        return Ok(());
+    }
+
+    // Before the runtime spawns any threads.
+    let fds = ListenFds::from_env();
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(upgrade_serve(fds))
+}
+
+#[cfg(unix)]
+async fn upgrade_serve(mut fds: ListenFds) -> Result<()> {
+    // The notifier is not passed as a listener.
+    assert_eq!(fds.len(), 1);
+    let idx = fds.position("http").expect("the inherited listener");
+    let listener = fds.take_tcp(idx)?.expect("the inherited listener");
+
+    let quit = Arc::new(tokio::sync::Notify::new());
+    let router = Router::new()
+        .get("/", |_: Request| async { Ok("new") })
+        .get("/quit", {
+            let quit = quit.clone();
+            move |_: Request| {
+                let quit = quit.clone();
+                async move {
+                    quit.notify_one();
+                    Ok(())
+                }
+            }
+        });
+    let server = serve(listener, router).signal(async move { quit.notified().await });
+
+    assert!(fds.notify_ready()?);
+    server.await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn trusted_proxies() -> Result<()> {
+    use viz::types::{IpNet, TrustedProxies};
+
+    async fn realip(trusted: &str) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let router = Router::new().get("/", |req: Request| async move {
+            Ok(req.realip().map(|ip| ip.0.to_string()).unwrap_or_default())
+        });
+        let proxies = TrustedProxies::new().trust(trusted.parse::<IpNet>().map_err(Error::boxed)?);
+        let server = tokio::spawn(
+            serve(listener, router)
+                .trusted_proxies(proxies)
+                .into_future(),
+        );
+
+        let resp = reqwest::Client::new()
+            .get(format!("http://{addr}/"))
+            .header("x-forwarded-for", "203.0.113.7")
+            .send()
+            .await
+            .map_err(Error::boxed)?;
+        server.abort();
+        resp.text().await.map_err(Error::boxed)
+    }
+
+    assert_eq!(realip("127.0.0.0/8").await?, "203.0.113.7");
+    // The forwarded headers of an untrusted peer are ignored.
+    assert_eq!(realip("10.0.0.0/8").await?, "127.0.0.1");
+
+    Ok(())
+}
@@ -526,14 +526,23 @@ mod responder;
 pub use responder::Responder;
 
 mod listener;
-pub use listener::Listener;
+pub use listener::{Either, Listener};
 
 mod server;
-pub use server::{Server, serve};
+pub use server::{ProxyProtocol, Server, ServerHandle, ServerStats, ShutdownSummary, serve};
+
+#[cfg(feature = "listenfd")]
+pub use server::ListenFds;
+
+#[cfg(all(unix, feature = "upgrade"))]
+pub use server::Upgrade;
 
 #[cfg(any(feature = "native-tls", feature = "rustls"))]
 pub use server::tls;
 
+#[cfg(feature = "http3")]
+pub use server::http3;
+
 pub use viz_core::*;
 pub use viz_router::*;
 
@@ -1,4 +1,10 @@
-/// A trait for a listener: `TcpListener` and `UnixListener`.
+use std::{future::Future, io::Result};
+
+use tokio::sync::mpsc::Sender;
+
+pub use tokio_util::either::Either;
+
+/// A trait for a listener: `TcpListener`, `UnixListener` and a pair of listeners.
 pub trait Listener {
     /// The stream's type of this listener.
     type Io;
@@ -6,9 +12,29 @@ pub trait Listener {
     type Addr;
 
     /// Accepts a new incoming connection from this listener.
-    fn accept(
-        &self,
-    ) -> impl std::future::Future<Output = std::io::Result<(Self::Io, Self::Addr)>> + Send;
+    fn accept(&self) -> impl Future<Output = Result<(Self::Io, Self::Addr)>> + Send;
+
+    /// Accepts the incoming connections and sends them until the receiver is dropped, which is
+    /// how the server drives the listener in its own task.
+    ///
+    /// The next connection is accepted once the previous one is received. The listeners which
+    /// wrap others override it, so the accepts are never cancelled and the handshakes, e.g. TLS,
+    /// run concurrently.
+    fn incoming(self, tx: Sender<Result<(Self::Io, Self::Addr)>>) -> impl Future<Output = ()> + Send
+    where
+        Self: Sized + Send,
+        Self::Io: Send,
+        Self::Addr: Send,
+    {
+        async move {
+            while let Ok(permit) = tx.reserve().await {
+                tokio::select! {
+                    conn = self.accept() => permit.send(conn),
+                    () = tx.closed() => break,
+                }
+            }
+        }
+    }
 
     /// Returns the local address that this listener is bound to.
     ///
@@ -16,5 +42,24 @@ pub trait Listener {
     ///
     /// An error will return if got the socket address of the local half of this connection is
     /// failed.
-    fn local_addr(&self) -> std::io::Result<Self::Addr>;
+    fn local_addr(&self) -> Result<Self::Addr>;
+
+    /// Returns the socket address of the peer, which is seen by the handlers as
+    /// [`remote_addr`](crate::RequestExt::remote_addr) and used for limiting the connections
+    /// per IP.
+    ///
+    /// Returns `None` by default, e.g. the peers of Unix sockets have no socket addresses.
+    fn peer_addr(addr: &Self::Addr) -> Option<std::net::SocketAddr> {
+        let _ = addr;
+        None
+    }
+
+    /// Returns the TLS information of the connection, which is seen by the handlers as
+    /// [`tls_info`](crate::RequestExt::tls_info).
+    ///
+    /// Returns `None` by default, the TLS listeners override it.
+    fn tls_info(io: &Self::Io) -> Option<crate::types::TlsInfo> {
+        let _ = io;
+        None
+    }
 }
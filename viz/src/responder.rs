@@ -1,12 +1,27 @@
-use std::{convert::Infallible, future::Future, pin::Pin, sync::Arc};
+use std::{convert::Infallible, future::Future, net::SocketAddr, pin::Pin, sync::Arc};
 
-use crate::{Body, Handler, Incoming, IntoResponse, Method, Request, Response, StatusCode, Tree};
+use crate::{
+    Body, Handler, Incoming, IntoResponse, Method, Normalized, Request, Response, ResponseExt,
+    StatusCode, Tree,
+    header::{ALT_SVC, HOST, HeaderValue},
+    headers::{Allow, HeaderMapExt},
+    server::State,
+    timeout::Watch,
+    types::{TlsInfo, TrustedProxies},
+};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
 /// Handles the HTTP [`Request`] and retures the HTTP [`Response`].
 #[derive(Debug)]
 pub struct Responder<A> {
-    tree: Arc<Tree>,
+    state: Arc<State>,
     remote_addr: Option<A>,
+    peer_addr: Option<SocketAddr>,
+    tls_info: Option<TlsInfo>,
+    watch: Option<Arc<Watch>>,
+    alt_svc: Option<HeaderValue>,
+    trusted_proxies: Option<TrustedProxies>,
 }
 
 impl<A> Responder<A>
@@ -16,7 +31,50 @@ where
     /// Creates a Responder for handling the [`Request`].
     #[must_use]
     pub fn new(tree: Arc<Tree>, remote_addr: Option<A>) -> Self {
-        Self { tree, remote_addr }
+        Self::shared(Arc::new(State::new(tree)), remote_addr)
+    }
+
+    /// Creates a Responder with the state of the server, the route tree is read on each request.
+    pub(crate) const fn shared(state: Arc<State>, remote_addr: Option<A>) -> Self {
+        Self {
+            state,
+            remote_addr,
+            peer_addr: None,
+            tls_info: None,
+            watch: None,
+            alt_svc: None,
+            trusted_proxies: None,
+        }
+    }
+
+    /// Sets the socket address of the peer, which is seen by the handlers as the remote address.
+    pub(crate) const fn peer_addr(mut self, peer_addr: Option<SocketAddr>) -> Self {
+        self.peer_addr = peer_addr;
+        self
+    }
+
+    /// Sets the TLS information of the connection.
+    pub(crate) fn tls_info(mut self, tls_info: Option<TlsInfo>) -> Self {
+        self.tls_info = tls_info;
+        self
+    }
+
+    /// Applies the timeouts of the connection to the requests.
+    pub(crate) fn watch(mut self, watch: Arc<Watch>) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+
+    /// Sets the `Alt-Svc` header of the responses, which advertises the alternative services.
+    pub(crate) fn alt_svc(mut self, alt_svc: Option<HeaderValue>) -> Self {
+        self.alt_svc = alt_svc;
+        self
+    }
+
+    /// Sets the proxies whose forwarded headers are honored by the handlers.
+    pub(crate) fn trusted_proxies(mut self, trusted_proxies: Option<TrustedProxies>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
     }
 }
 
@@ -26,38 +84,123 @@ where
 {
     type Response = Response;
     type Error = Infallible;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn call(&self, req: Request<Incoming>) -> Self::Future {
+        let res = self.respond(req.map(Body::Incoming));
+        let res: BoxFuture<Response> = match self.alt_svc.clone() {
+            Some(alt_svc) => Box::pin(async move {
+                let mut res = res.await;
+                res.headers_mut().entry(ALT_SVC).or_insert(alt_svc);
+                res
+            }),
+            None => res,
+        };
+
+        match self.watch.clone() {
+            Some(watch) => Box::pin(async move { Ok(watch.request(res).await) }),
+            None => Box::pin(async move { Ok(res.await) }),
+        }
+    }
+}
 
-    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+impl<A> Responder<A>
+where
+    A: Clone + Send + Sync + 'static,
+{
+    /// Routes the request and calls the handler.
+    pub(crate) fn respond(&self, mut req: Request) -> BoxFuture<Response> {
         let method = req.method().clone();
-        let path = req.uri().path().to_owned();
+        let mut path = req.uri().path().to_owned();
+        let host = req
+            .uri()
+            .host()
+            .or_else(|| req.headers().get(HOST).and_then(|v| v.to_str().ok()))
+            .map(ToOwned::to_owned);
+        let tree = self.state.tree();
+        let (tree, host_params) = tree.select(host.as_deref());
+
+        match tree.normalize(&path) {
+            Some(Normalized::Redirect(mut location)) => {
+                if let Some(query) = req.uri().query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+                return Box::pin(async move { Response::permanent(location) });
+            }
+            Some(Normalized::Rewrite(rewritten)) => path = rewritten,
+            None => {}
+        }
 
-        let Some((handler, route)) = self.tree.find(&method, &path).or_else(|| {
+        let found = tree.find(&method, &path).or_else(|| {
             if method == Method::HEAD {
-                self.tree.find(&Method::GET, &path)
+                tree.find(&Method::GET, &path)
+            } else {
+                None
+            }
+        });
+        let allowed = if found.is_none() {
+            tree.allowed(&path)
+        } else {
+            Vec::new()
+        };
+        // The fallback only handles the paths matching no verbs.
+        let Some((handler, route)) = found.or_else(|| {
+            if allowed.is_empty() {
+                tree.fallback(&path)
             } else {
                 None
             }
         }) else {
-            return Box::pin(async move { Ok(StatusCode::NOT_FOUND.into_response()) });
+            return Box::pin(async move { not_matched(allowed) });
         };
 
         let extensions = req.extensions_mut();
 
         extensions.insert(self.remote_addr.clone());
+        if let Some(peer_addr) = self.peer_addr {
+            extensions.insert(peer_addr);
+        }
+        if let Some(tls_info) = self.tls_info.clone() {
+            extensions.insert(tls_info);
+        }
+        if let Some(trusted_proxies) = self.trusted_proxies.clone() {
+            extensions.insert(trusted_proxies);
+        }
+        extensions.insert(tree.urls().clone());
         extensions.insert(Arc::from(crate::types::RouteInfo {
             id: *route.id,
             pattern: route.pattern(),
-            params: route.params().into(),
+            params: host_params
+                .into_iter()
+                .chain(route.params())
+                .collect::<Vec<_>>()
+                .into(),
         }));
 
         let handler = handler.clone();
+        let in_flight = self.state.track(|state| &state.in_flight);
 
         Box::pin(async move {
-            handler
-                .call(req.map(Body::Incoming))
+            let res = handler
+                .call(req)
                 .await
-                .or_else(|e| Ok(e.into_response()))
+                .unwrap_or_else(IntoResponse::into_response);
+            drop(in_flight);
+            res
         })
     }
 }
+
+/// Responds `404 Not Found` if no route matches the path, otherwise `405 Method Not Allowed`
+/// with the `Allow` header listing the verbs registered for the path.
+fn not_matched(allowed: Vec<Method>) -> Response {
+    if allowed.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let mut res = StatusCode::METHOD_NOT_ALLOWED.into_response();
+    res.headers_mut()
+        .typed_insert(allowed.into_iter().collect::<Allow>());
+    res
+}
@@ -3,30 +3,70 @@ use std::{
     future::{Future, IntoFuture, Pending, pending},
     io,
     pin::{Pin, pin},
-    sync::Arc,
+    sync::{Arc, atomic::Ordering},
     time::Duration,
 };
 
 #[cfg(any(feature = "http1", feature = "http2"))]
 use hyper_util::server::conn::auto::Builder;
 use hyper_util::{
-    rt::{TokioExecutor, TokioIo},
+    rt::{TokioExecutor, TokioIo, TokioTimer},
     server::graceful::GracefulShutdown,
 };
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{OwnedSemaphorePermit, Semaphore, mpsc},
+};
 
-use crate::{Listener, Responder, Router};
+use crate::{
+    Conflicts, Listener, Responder, Router, Tree,
+    header::HeaderValue,
+    timeout::{Timeouts, Watch, Watched},
+    types::TrustedProxies,
+};
 
 /// TLS
 #[cfg(any(feature = "native-tls", feature = "rustls"))]
 pub mod tls;
 
+/// HTTP/3
+#[cfg(feature = "http3")]
+pub mod http3;
+
 #[cfg(any(feature = "http1", feature = "http2"))]
 mod tcp;
 
 #[cfg(all(unix, feature = "unix-socket"))]
 mod unix;
 
+mod either;
+
+mod handshake;
+
+#[cfg(feature = "listenfd")]
+mod listenfd;
+#[cfg(feature = "listenfd")]
+pub use listenfd::ListenFds;
+
+#[cfg(all(unix, feature = "upgrade"))]
+mod upgrade;
+#[cfg(all(unix, feature = "upgrade"))]
+pub use upgrade::Upgrade;
+
+mod proxy_protocol;
+pub use proxy_protocol::ProxyProtocol;
+
+mod handle;
+pub(crate) use handle::State;
+pub use handle::{ServerHandle, ServerStats};
+
+mod shutdown;
+pub use shutdown::ShutdownSummary;
+use shutdown::{Connections, Hook};
+
+mod limit;
+use limit::PeerLimit;
+
 /// Starts a server and serves the connections.
 pub fn serve<L>(listener: L, router: Router) -> Server<L> {
     Server::<L>::new(listener, router)
@@ -37,23 +77,66 @@ pub fn serve<L>(listener: L, router: Router) -> Server<L> {
 pub struct Server<L, S = Pending<()>> {
     listener: L,
     signal: S,
-    tree: crate::Tree,
+    state: Arc<State>,
     builder: Builder<TokioExecutor>,
+    options: Options,
+}
+
+/// The options of the server.
+#[derive(Debug)]
+struct Options {
+    shutdown_timeout: Duration,
+    on_shutdown: Vec<Hook>,
+    on_shutdown_complete: Vec<Hook>,
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    timeouts: Timeouts,
+    alt_svc: Option<HeaderValue>,
+    trusted_proxies: Option<TrustedProxies>,
+    /// The conflicts of the strict router, which fail the server.
+    conflicts: Option<Conflicts>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            shutdown_timeout: Duration::from_secs(10),
+            on_shutdown: Vec::new(),
+            on_shutdown_complete: Vec::new(),
+            max_connections: None,
+            max_connections_per_ip: None,
+            timeouts: Timeouts::default(),
+            alt_svc: None,
+            trusted_proxies: None,
+            conflicts: None,
+        }
+    }
 }
 
 impl<L> Server<L> {
     /// Starts a [`Server`] with a listener and a [`Router`].
+    ///
+    /// If the router is [strict](Router::strict) and any routes conflict, the server fails to
+    /// run with an [`InvalidInput`](io::ErrorKind::InvalidInput) error.
     pub fn new(listener: L, router: Router) -> Self {
         Self::with_builder(listener, router, Builder::new(TokioExecutor::new()))
     }
 
     /// Starts a [`Server`] with a listener, a [`Router`] and a [`Builder`].
     pub fn with_builder(listener: L, router: Router, builder: Builder<TokioExecutor>) -> Self {
+        let (tree, conflicts) = match Tree::try_build(router) {
+            Ok(tree) => (tree, None),
+            Err(conflicts) => (Tree::default(), Some(conflicts)),
+        };
         Server {
             listener,
             builder,
             signal: pending(),
-            tree: router.into(),
+            state: Arc::new(State::new(Arc::new(tree))),
+            options: Options {
+                conflicts,
+                ..Options::default()
+            },
         }
     }
 
@@ -61,40 +144,182 @@ impl<L> Server<L> {
     pub fn signal<S>(self, signal: S) -> Server<L, S> {
         Server {
             signal,
-            tree: self.tree,
+            state: self.state,
             builder: self.builder,
             listener: self.listener,
+            options: self.options,
+        }
+    }
+}
+
+impl<L, S> Server<L, S> {
+    /// Returns a cloneable handle for controlling and observing the server while it is
+    /// running, e.g. triggering the shutdown without the signal.
+    #[must_use]
+    pub fn handle(&self) -> ServerHandle<L::Addr>
+    where
+        L: Listener,
+    {
+        ServerHandle {
+            state: self.state.clone(),
+            local_addr: self.listener.local_addr().ok().map(Arc::new),
         }
     }
+
+    /// Specifies how long to wait for the connections to be drained on shutdown,
+    /// defaults to 10 seconds.
+    ///
+    /// The connections still open after the timeout are force-closed.
+    #[must_use]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.options.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Adds a hook fired when the signal is received, before draining the connections.
+    #[must_use]
+    pub fn on_shutdown<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.options.on_shutdown.push(Hook::new(f));
+        self
+    }
+
+    /// Adds a hook fired when the connections are drained or force-closed.
+    #[must_use]
+    pub fn on_shutdown_complete<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.options.on_shutdown_complete.push(Hook::new(f));
+        self
+    }
+
+    /// Limits the number of the concurrent connections, accepting new connections is paused
+    /// until an open one is closed.
+    #[must_use]
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.options.max_connections = Some(max);
+        self
+    }
+
+    /// Limits the number of the concurrent connections of each peer IP, the connections
+    /// over the limit are closed immediately and counted as rejected.
+    ///
+    /// Only applies to the listeners which can tell the peer IP, see [`Listener::peer_addr`].
+    #[must_use]
+    pub fn max_connections_per_ip(mut self, max: usize) -> Self {
+        self.options.max_connections_per_ip = Some(max);
+        self
+    }
+
+    /// Specifies how long to wait for the header of a request on HTTP/1 connections, including
+    /// the next request on a kept-alive connection.
+    ///
+    /// `408 Request Timeout` is responded if a part of the header is read in time, otherwise the
+    /// connection is closed. The idle HTTP/2 connections are closed by the
+    /// [idle timeout](Self::idle_timeout).
+    #[must_use]
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.header_read = Some(timeout);
+        self
+    }
+
+    /// Specifies how long a connection can be idle between the requests before it is closed.
+    ///
+    /// The connection is not idle until the responses, including their bodies, are finished.
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.idle = Some(timeout);
+        self
+    }
+
+    /// Specifies how long a handler can take to produce the response, otherwise it is
+    /// cancelled and `503 Service Unavailable` is responded.
+    #[must_use]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.request = Some(timeout);
+        self
+    }
+
+    /// Responds the `Alt-Svc` header, if the handlers have not, to advertise the alternative
+    /// services of the same origin, e.g. an HTTP/3 server.
+    #[must_use]
+    pub fn alt_svc(mut self, value: HeaderValue) -> Self {
+        self.options.alt_svc = Some(value);
+        self
+    }
+
+    /// Honors the forwarded headers sent by the proxies, which are seen by the handlers as
+    /// [`realip`](crate::RequestExt::realip) and [`forwarded`](crate::RequestExt::forwarded).
+    ///
+    /// Without it, [`realip`](crate::RequestExt::realip) trusts the headers from any client.
+    #[must_use]
+    pub fn trusted_proxies(mut self, proxies: TrustedProxies) -> Self {
+        self.options.trusted_proxies = Some(proxies);
+        self
+    }
 }
 
 impl<L, S> IntoFuture for Server<L, S>
 where
     L: Listener + Send + 'static,
-    L::Io: AsyncRead + AsyncWrite + Send + Unpin,
-    L::Addr: Send + Sync + Debug,
+    L::Io: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    L::Addr: Send + Sync + Debug + 'static,
     S: Future + Send + 'static,
     S::Output: Send,
 {
-    type Output = io::Result<()>;
+    type Output = io::Result<ShutdownSummary<L::Addr>>;
     type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send>>;
 
     fn into_future(self) -> Self::IntoFuture {
         let Self {
-            tree,
+            state,
             signal,
             builder,
             listener,
+            options:
+                Options {
+                    shutdown_timeout,
+                    on_shutdown,
+                    on_shutdown_complete,
+                    max_connections,
+                    max_connections_per_ip,
+                    timeouts,
+                    alt_svc,
+                    trusted_proxies,
+                    conflicts,
+                },
         } = self;
 
         Box::pin(async move {
+            if let Some(conflicts) = conflicts {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, conflicts));
+            }
+
             let graceful = GracefulShutdown::new();
-            let tree = Arc::new(tree);
-            let mut signal = pin!(signal);
+            let conns = Arc::new(Connections::new());
+            let permits = max_connections.map(|max| Arc::new(Semaphore::new(max)));
+            let peers = max_connections_per_ip.map(|max| Arc::new(PeerLimit::new(max)));
+            let shutdown = state.shutdown.clone();
+            let mut signal = pin!(async move {
+                tokio::select! {
+                    _ = signal => {},
+                    () = shutdown.cancelled() => {},
+                }
+            });
+
+            // The listener is driven in its own task, its accepts are never cancelled.
+            let (tx, mut incoming) = mpsc::channel(1);
+            let accepting = tokio::spawn(listener.incoming(tx));
 
             loop {
                 tokio::select! {
-                    conn = listener.accept() => {
+                    (permit, conn) = accept(&mut incoming, permits.as_ref()) => {
+                        let Some(conn) = conn else {
+                            break;
+                        };
                         let (stream, peer_addr) = match conn {
                             Ok(conn) => conn,
                             Err(err) => {
@@ -108,42 +333,88 @@ where
 
                         tracing::trace!("incomming connection accepted: {:?}", peer_addr);
 
+                        state.accepted.fetch_add(1, Ordering::Relaxed);
+
+                        let peer = match (&peers, L::peer_addr(&peer_addr).map(|addr| addr.ip())) {
+                            (Some(peers), Some(ip)) => {
+                                let Some(peer) = peers.acquire(ip) else {
+                                    state.rejected.fetch_add(1, Ordering::Relaxed);
+                                    tracing::debug!("connection rejected, too many connections from {ip}");
+                                    continue;
+                                };
+                                Some(peer)
+                            }
+                            _ => None,
+                        };
+
+                        let open = state.track(|state| &state.open);
+
                         let peer_addr = Arc::new(peer_addr);
 
-                        let stream = TokioIo::new(Box::pin(stream));
+                        let tls_info = L::tls_info(&stream);
+
+                        let watch = Watch::new(timeouts, TokioTimer::new());
 
-                        let responder = Responder::new(tree.clone(), Some(peer_addr.clone()));
+                        let stream = Watched::new(TokioIo::new(Box::pin(stream)), watch.clone());
+
+                        let responder = Responder::shared(state.clone(), Some(peer_addr.clone()))
+                            .peer_addr(L::peer_addr(&peer_addr))
+                            .tls_info(tls_info)
+                            .watch(watch)
+                            .alt_svc(alt_svc.clone())
+                            .trusted_proxies(trusted_proxies.clone());
 
                         let conn = builder.serve_connection_with_upgrades(stream, responder);
 
                         let conn = graceful.watch(conn.into_owned());
 
-                        tokio::spawn(async move {
+                        conns.spawn(peer_addr.clone(), async move {
                             if let Err(err) = conn.await {
                                 tracing::error!("connection error: {}", err);
                             }
                             tracing::trace!("connection dropped: {:?}", peer_addr);
+                            drop((open, peer, permit));
                         });
                     },
 
-                    _ = signal.as_mut() => {
-                        drop(listener);
+                    () = signal.as_mut() => {
                         tracing::trace!("Signal received, starting shutdown");
                         break;
                     }
                 }
             }
 
-            tokio::select! {
+            state.shutdown.cancel();
+            // The listener is closed before draining the connections.
+            accepting.abort();
+            let _ = accepting.await;
+
+            on_shutdown.into_iter().for_each(Hook::call);
+
+            // Keep-alive connections are sent `Connection: close` or HTTP/2 `GOAWAY`.
+            let open = conns.len();
+            let forced = tokio::select! {
                 () = graceful.shutdown() => {
                     tracing::trace!("Gracefully shutdown!");
+                    Vec::new()
                 },
-                () = tokio::time::sleep(Duration::from_secs(10)) => {
-                    tracing::error!("Waited 10 seconds for graceful shutdown, aborting...");
+                () = tokio::time::sleep(shutdown_timeout) => {
+                    let forced = conns.abort_all();
+                    tracing::error!(
+                        "Waited {:?} for graceful shutdown, force-closed {} connections",
+                        shutdown_timeout,
+                        forced.len()
+                    );
+                    forced
                 }
-            }
+            };
 
-            Ok(())
+            on_shutdown_complete.into_iter().for_each(Hook::call);
+
+            Ok(ShutdownSummary {
+                drained: open.saturating_sub(forced.len()),
+                forced,
+            })
         })
     }
 }
@@ -156,3 +427,18 @@ fn is_connection_error(e: &io::Error) -> bool {
             | io::ErrorKind::ConnectionReset
     )
 }
+
+/// Receives a new connection, waits for a permit first if the connections are limited.
+///
+/// Returns `None` if the listener stops accepting.
+async fn accept<T>(
+    incoming: &mut mpsc::Receiver<T>,
+    permits: Option<&Arc<Semaphore>>,
+) -> (Option<OwnedSemaphorePermit>, Option<T>) {
+    let permit = match permits {
+        // The semaphore is never closed.
+        Some(permits) => permits.clone().acquire_owned().await.ok(),
+        None => None,
+    };
+    (permit, incoming.recv().await)
+}
@@ -0,0 +1,87 @@
+use std::{io::Result, net::SocketAddr};
+
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::{Either, types::TlsInfo};
+
+/// Accepts the connections from both listeners, e.g. serving the same router on TCP and Unix
+/// sockets, or on plain HTTP and TLS ports.
+///
+/// The pairs can be nested for more than two listeners. The server drives each listener by
+/// its own [`incoming`](super::Listener::incoming), while [`accept`](super::Listener::accept)
+/// drops the pending accept of the other listener, which may lose a connection during its
+/// handshake.
+impl<A, B> super::Listener for (A, B)
+where
+    A: super::Listener + Send + Sync,
+    A::Io: Send,
+    A::Addr: Send,
+    B: super::Listener + Send + Sync,
+    B::Io: Send,
+    B::Addr: Send,
+{
+    type Io = Either<A::Io, B::Io>;
+    type Addr = Either<A::Addr, B::Addr>;
+
+    async fn accept(&self) -> Result<(Self::Io, Self::Addr)> {
+        tokio::select! {
+            conn = self.0.accept() => {
+                conn.map(|(io, addr)| (Either::Left(io), Either::Left(addr)))
+            }
+            conn = self.1.accept() => {
+                conn.map(|(io, addr)| (Either::Right(io), Either::Right(addr)))
+            }
+        }
+    }
+
+    async fn incoming(self, tx: Sender<Result<(Self::Io, Self::Addr)>>)
+    where
+        Self: Sized + Send,
+        Self::Io: Send,
+        Self::Addr: Send,
+    {
+        let (a, b) = self;
+        let (left_tx, mut left) = mpsc::channel::<Result<(A::Io, A::Addr)>>(1);
+        let (right_tx, mut right) = mpsc::channel::<Result<(B::Io, B::Addr)>>(1);
+
+        let forward = async move {
+            loop {
+                // Receiving is cancel safe, the connections are kept in the channels.
+                let conn = tokio::select! {
+                    Some(conn) = left.recv() => {
+                        conn.map(|(io, addr)| (Either::Left(io), Either::Left(addr)))
+                    }
+                    Some(conn) = right.recv() => {
+                        conn.map(|(io, addr)| (Either::Right(io), Either::Right(addr)))
+                    }
+                    () = tx.closed() => break,
+                    else => break,
+                };
+                if tx.send(conn).await.is_err() {
+                    break;
+                }
+            }
+        };
+
+        tokio::join!(a.incoming(left_tx), b.incoming(right_tx), forward);
+    }
+
+    /// Returns the local address of the first listener.
+    fn local_addr(&self) -> Result<Self::Addr> {
+        self.0.local_addr().map(Either::Left)
+    }
+
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        match addr {
+            Either::Left(addr) => A::peer_addr(addr),
+            Either::Right(addr) => B::peer_addr(addr),
+        }
+    }
+
+    fn tls_info(io: &Self::Io) -> Option<TlsInfo> {
+        match io {
+            Either::Left(io) => A::tls_info(io),
+            Either::Right(io) => B::tls_info(io),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc, PoisonError, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{Conflicts, Router, Tree};
+
+/// The state shared by the server, its handles and responders.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    tree: RwLock<Arc<Tree>>,
+    pub(crate) shutdown: CancellationToken,
+    pub(crate) accepted: AtomicU64,
+    pub(crate) rejected: AtomicU64,
+    pub(crate) open: AtomicUsize,
+    pub(crate) in_flight: AtomicUsize,
+}
+
+impl State {
+    pub(crate) fn new(tree: Arc<Tree>) -> Self {
+        Self {
+            tree: RwLock::new(tree),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn tree(&self) -> Arc<Tree> {
+        self.tree
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Increments the gauge, which is decremented when the returned guard is dropped.
+    pub(crate) fn track(self: &Arc<Self>, gauge: fn(&Self) -> &AtomicUsize) -> Tracked {
+        gauge(self).fetch_add(1, Ordering::Relaxed);
+        Tracked {
+            state: self.clone(),
+            gauge,
+        }
+    }
+}
+
+/// Decrements the gauge of the state on drop.
+pub(crate) struct Tracked {
+    state: Arc<State>,
+    gauge: fn(&State) -> &AtomicUsize,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        (self.gauge)(&self.state).fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The counters of a running server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// The number of the open connections.
+    pub open_connections: usize,
+    /// The total number of the accepted connections.
+    pub accepted: u64,
+    /// The total number of the connections rejected by the per-IP limit.
+    pub rejected: u64,
+    /// The number of the requests being handled.
+    pub in_flight: usize,
+}
+
+/// A cloneable handle for controlling and observing a [`Server`](super::Server) while it is
+/// running.
+#[derive(Debug)]
+pub struct ServerHandle<A = SocketAddr> {
+    pub(crate) state: Arc<State>,
+    pub(crate) local_addr: Option<Arc<A>>,
+}
+
+impl<A> Clone for ServerHandle<A> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            local_addr: self.local_addr.clone(),
+        }
+    }
+}
+
+impl<A> ServerHandle<A> {
+    /// Returns the local address that the server is bound to.
+    #[must_use]
+    pub fn local_addr(&self) -> Option<&A> {
+        self.local_addr.as_deref()
+    }
+
+    /// Returns the current counters of the server.
+    #[must_use]
+    pub fn stats(&self) -> ServerStats {
+        ServerStats {
+            open_connections: self.state.open.load(Ordering::Relaxed),
+            accepted: self.state.accepted.load(Ordering::Relaxed),
+            rejected: self.state.rejected.load(Ordering::Relaxed),
+            in_flight: self.state.in_flight.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Triggers the graceful shutdown, as if the signal of the server was received.
+    pub fn shutdown(&self) {
+        self.state.shutdown.cancel();
+    }
+
+    /// Returns `true` if the shutdown has been triggered.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        self.state.shutdown.is_cancelled()
+    }
+
+    /// Returns the current route tree.
+    #[must_use]
+    pub fn tree(&self) -> Arc<Tree> {
+        self.state.tree()
+    }
+
+    /// Atomically replaces the routes of the server.
+    ///
+    /// The requests arriving afterwards, including the ones on the kept-alive connections,
+    /// are served by the new routes, while the in-flight requests finish with the old ones.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Conflicts`] if the router is [strict](Router::strict) and any routes
+    /// conflict, the current routes are kept.
+    pub fn swap_router(&self, router: Router) -> Result<(), Conflicts> {
+        self.swap_tree(Tree::try_build(router)?);
+        Ok(())
+    }
+
+    /// Atomically replaces the route tree of the server, returns the old one.
+    pub fn swap_tree(&self, tree: Tree) -> Arc<Tree> {
+        std::mem::replace(
+            &mut *self
+                .state
+                .tree
+                .write()
+                .unwrap_or_else(PoisonError::into_inner),
+            Arc::new(tree),
+        )
+    }
+}
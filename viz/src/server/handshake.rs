@@ -0,0 +1,91 @@
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+use tokio::{
+    sync::mpsc::{self, Sender},
+    task::JoinSet,
+};
+
+use crate::Listener;
+
+/// The limits of the pending handshakes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Limits {
+    /// How long a handshake can take.
+    pub(crate) timeout: Duration,
+    /// How many handshakes can be pending, accepting is paused once reached.
+    pub(crate) max: usize,
+}
+
+impl Limits {
+    pub(crate) const fn new(timeout: Duration) -> Self {
+        Self { timeout, max: 1024 }
+    }
+
+    /// Runs the handshake, fails if it takes too long.
+    pub(crate) async fn run<F, T>(self, handshake: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        tokio::time::timeout(self.timeout, handshake)
+            .await
+            .unwrap_or_else(|_| Err(Error::new(ErrorKind::TimedOut, "handshake timed out")))
+    }
+}
+
+/// Accepts the connections of the inner listener, runs their handshakes concurrently and sends
+/// the established ones, so a stalled client does not delay the others.
+///
+/// The failed and timed out handshakes are dropped, the pending ones are aborted once the
+/// receiver is dropped. Accepting is paused while the pending handshakes reach the limit.
+pub(crate) async fn incoming<L, F, Fut, Io, Addr>(
+    inner: L,
+    tx: Sender<Result<(Io, Addr)>>,
+    limits: Limits,
+    handshake: F,
+) where
+    L: Listener + Send,
+    L::Io: Send,
+    L::Addr: Send,
+    F: Fn(L::Io, L::Addr) -> Fut + Send,
+    Fut: Future<Output = Result<(Io, Addr)>> + Send + 'static,
+    Io: Send + 'static,
+    Addr: Send + 'static,
+{
+    let (raw_tx, mut raw) = mpsc::channel::<Result<(L::Io, L::Addr)>>(1);
+
+    let handshaking = async move {
+        let mut handshakes = JoinSet::new();
+        loop {
+            tokio::select! {
+                conn = raw.recv(), if handshakes.len() < limits.max => match conn {
+                    Some(Ok((io, addr))) => {
+                        let handshake = limits.run(handshake(io, addr));
+                        let tx = tx.clone();
+                        handshakes.spawn(async move {
+                            match handshake.await {
+                                Ok(conn) => {
+                                    let _ = tx.send(Ok(conn)).await;
+                                }
+                                Err(err) => tracing::debug!("handshake error: {err}"),
+                            }
+                        });
+                    }
+                    Some(Err(err)) => {
+                        if tx.send(Err(err)).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                Some(_) = handshakes.join_next() => {},
+                () = tx.closed() => break,
+            }
+        }
+    };
+
+    tokio::join!(inner.incoming(raw_tx), handshaking);
+}
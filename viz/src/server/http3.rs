@@ -0,0 +1,543 @@
+//! HTTP/3 over QUIC.
+
+use std::{
+    fmt,
+    future::{Future, IntoFuture, Pending, pending},
+    io,
+    net::SocketAddr,
+    pin::{Pin, pin},
+    sync::{Arc, atomic::Ordering},
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use bytes::Buf;
+use h3::{
+    error::{Code, StreamError},
+    server::RequestStream,
+};
+use hyper::body::Frame;
+use hyper_util::rt::TokioTimer;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinSet,
+    time::{Instant, sleep_until},
+};
+use tokio_rustls::rustls::{ServerConfig, pki_types::CertificateDer};
+
+use crate::{
+    Body, Bytes, Conflicts, HttpBody, Method, Request, Responder, Response, Router, Tree,
+    header::{CONNECTION, HeaderValue, TRANSFER_ENCODING},
+    server::{ServerHandle, ShutdownSummary, State, limit::PeerLimit, shutdown::Connections},
+    timeout::{Timeouts, Watch},
+    types::TlsInfo,
+};
+
+pub use quinn;
+
+/// The ALPN protocol of HTTP/3.
+pub const ALPN: &[u8] = b"h3";
+
+/// A listening HTTP/3 server over QUIC, which serves the same [`Router`] as the TCP
+/// [`Server`](crate::Server), the handlers see no difference but the [`Request::version`].
+///
+/// The clients discover it via the `Alt-Svc` header responded by the TCP server, see
+/// [`Server::alt_svc`](crate::Server::alt_svc).
+///
+/// The connection limits and the idle and request timeouts are applied as by the TCP server,
+/// there is no header read timeout, the QUIC handshakes are limited by the transport config
+/// of the endpoint instead.
+///
+/// ```no_run
+/// # async fn run(config: tokio_rustls::rustls::ServerConfig) -> viz::Result<()> {
+/// use tokio::net::TcpListener;
+/// use viz::{Request, Router, http3, serve, tls};
+///
+/// let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 3000));
+/// let router = Router::new().get("/", |_: Request| async { Ok("Hello, World!") });
+///
+/// let quic = http3::Server::bind(addr, config.clone(), router.clone())?;
+/// let alt_svc = quic.alt_svc()?;
+/// tokio::spawn(quic.into_future());
+///
+/// let listener = tls::TlsListener::new(
+///     TcpListener::bind(addr).await?,
+///     tls::rustls::TlsAcceptor::from(std::sync::Arc::new(config)),
+/// );
+/// serve(listener, router).alt_svc(alt_svc).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Server<S = Pending<()>> {
+    endpoint: quinn::Endpoint,
+    signal: S,
+    state: Arc<State>,
+    options: Options,
+}
+
+/// The options of the server.
+#[derive(Debug)]
+struct Options {
+    shutdown_timeout: Duration,
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    timeouts: Timeouts,
+    /// The conflicts of the strict router, which fail the server.
+    conflicts: Option<Conflicts>,
+}
+
+impl Server {
+    /// Starts a [`Server`] with a QUIC endpoint and a [`Router`].
+    ///
+    /// The endpoint should be a server whose ALPN protocols include [`ALPN`]. If the router is
+    /// [strict](Router::strict) and any routes conflict, the server fails to run with an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error.
+    #[must_use]
+    pub fn new(endpoint: quinn::Endpoint, router: Router) -> Self {
+        let (tree, conflicts) = match Tree::try_build(router) {
+            Ok(tree) => (tree, None),
+            Err(conflicts) => (Tree::default(), Some(conflicts)),
+        };
+        Self {
+            endpoint,
+            signal: pending(),
+            state: Arc::new(State::new(Arc::new(tree))),
+            options: Options {
+                shutdown_timeout: Duration::from_secs(10),
+                max_connections: None,
+                max_connections_per_ip: None,
+                timeouts: Timeouts::default(),
+                conflicts,
+            },
+        }
+    }
+
+    /// Binds a QUIC endpoint to the UDP address and starts a [`Server`], the ALPN protocols
+    /// of the TLS config are replaced by [`ALPN`].
+    ///
+    /// The TLS config must support TLS 1.3, which is required by QUIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    pub fn bind(addr: SocketAddr, mut config: ServerConfig, router: Router) -> io::Result<Self> {
+        config.alpn_protocols = vec![ALPN.to_vec()];
+        let config =
+            quinn::crypto::rustls::QuicServerConfig::try_from(config).map_err(io::Error::other)?;
+        let endpoint =
+            quinn::Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(config)), addr)?;
+        Ok(Self::new(endpoint, router))
+    }
+
+    /// Specifies a signal for graceful shutdown.
+    pub fn signal<S>(self, signal: S) -> Server<S> {
+        Server {
+            signal,
+            endpoint: self.endpoint,
+            state: self.state,
+            options: self.options,
+        }
+    }
+}
+
+impl<S> Server<S> {
+    /// Returns a cloneable handle for controlling and observing the server while it is
+    /// running, e.g. triggering the shutdown without the signal.
+    #[must_use]
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            state: self.state.clone(),
+            local_addr: self.endpoint.local_addr().ok().map(Arc::new),
+        }
+    }
+
+    /// Specifies how long to wait for the connections to be drained on shutdown,
+    /// defaults to 10 seconds.
+    ///
+    /// The connections still open after the timeout are closed.
+    #[must_use]
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.options.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Limits the number of the concurrent connections, accepting new connections is paused
+    /// until an open one is closed.
+    #[must_use]
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.options.max_connections = Some(max);
+        self
+    }
+
+    /// Limits the number of the concurrent connections of each peer IP, the connections
+    /// over the limit are refused and counted as rejected.
+    #[must_use]
+    pub fn max_connections_per_ip(mut self, max: usize) -> Self {
+        self.options.max_connections_per_ip = Some(max);
+        self
+    }
+
+    /// Specifies how long a connection can be idle between the requests before it is closed.
+    ///
+    /// The connection is not idle until the responses, including their bodies, are finished.
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.idle = Some(timeout);
+        self
+    }
+
+    /// Specifies how long a handler can take to produce the response, otherwise it is
+    /// cancelled and `503 Service Unavailable` is responded.
+    #[must_use]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeouts.request = Some(timeout);
+        self
+    }
+
+    /// Returns the value of the `Alt-Svc` header advertising this server on the same host,
+    /// e.g. `h3=":443"; ma=86400`.
+    pub fn alt_svc(&self) -> io::Result<HeaderValue> {
+        let port = self.endpoint.local_addr()?.port();
+        HeaderValue::try_from(format!("h3=\":{port}\"; ma=86400")).map_err(io::Error::other)
+    }
+}
+
+impl<S> IntoFuture for Server<S>
+where
+    S: Future + Send + 'static,
+    S::Output: Send,
+{
+    type Output = io::Result<ShutdownSummary<SocketAddr>>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        let Self {
+            endpoint,
+            signal,
+            state,
+            options:
+                Options {
+                    shutdown_timeout,
+                    max_connections,
+                    max_connections_per_ip,
+                    timeouts,
+                    conflicts,
+                },
+        } = self;
+
+        Box::pin(async move {
+            if let Some(conflicts) = conflicts {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, conflicts));
+            }
+
+            let conns = Arc::new(Connections::new());
+            let permits = max_connections.map(|max| Arc::new(Semaphore::new(max)));
+            let peers = max_connections_per_ip.map(|max| Arc::new(PeerLimit::new(max)));
+            let shutdown = state.shutdown.clone();
+            let mut signal = pin!(async move {
+                tokio::select! {
+                    _ = signal => {},
+                    () = shutdown.cancelled() => {},
+                }
+            });
+
+            loop {
+                tokio::select! {
+                    (permit, incoming) = accept(&endpoint, permits.as_ref()) => {
+                        let Some(incoming) = incoming else {
+                            break;
+                        };
+                        state.accepted.fetch_add(1, Ordering::Relaxed);
+
+                        let peer_addr = incoming.remote_address();
+                        let peer = match &peers {
+                            Some(peers) => {
+                                let Some(peer) = peers.acquire(peer_addr.ip()) else {
+                                    state.rejected.fetch_add(1, Ordering::Relaxed);
+                                    tracing::debug!(
+                                        "connection refused, too many connections from {}",
+                                        peer_addr.ip()
+                                    );
+                                    incoming.refuse();
+                                    continue;
+                                };
+                                Some(peer)
+                            }
+                            None => None,
+                        };
+
+                        let conn = serve_connection(incoming, state.clone(), timeouts);
+                        conns.spawn(Arc::new(peer_addr), async move {
+                            conn.await;
+                            drop((peer, permit));
+                        });
+                    },
+
+                    () = signal.as_mut() => {
+                        tracing::trace!("Signal received, starting shutdown");
+                        break;
+                    }
+                }
+            }
+
+            state.shutdown.cancel();
+
+            // The new connections are refused, the open ones are sent `GOAWAY`.
+            endpoint.set_server_config(None);
+            let open = conns.len();
+            let forced = tokio::select! {
+                () = conns.drained() => Vec::new(),
+                () = tokio::time::sleep(shutdown_timeout) => {
+                    let forced = conns.abort_all();
+                    tracing::error!(
+                        "Waited {:?} for graceful shutdown, force-closed {} connections",
+                        shutdown_timeout,
+                        forced.len()
+                    );
+                    forced
+                }
+            };
+
+            endpoint.close(quinn::VarInt::from_u32(0), b"");
+            endpoint.wait_idle().await;
+
+            Ok(ShutdownSummary {
+                drained: open.saturating_sub(forced.len()),
+                forced,
+            })
+        })
+    }
+}
+
+/// Accepts a new connection, waits for a permit first if the connections are limited.
+///
+/// Returns `None` if the endpoint is closed.
+async fn accept(
+    endpoint: &quinn::Endpoint,
+    permits: Option<&Arc<Semaphore>>,
+) -> (Option<OwnedSemaphorePermit>, Option<quinn::Incoming>) {
+    let permit = match permits {
+        // The semaphore is never closed.
+        Some(permits) => permits.clone().acquire_owned().await.ok(),
+        None => None,
+    };
+    (permit, endpoint.accept().await)
+}
+
+/// Serves the requests of a QUIC connection.
+async fn serve_connection(incoming: quinn::Incoming, state: Arc<State>, timeouts: Timeouts) {
+    let conn = match incoming.await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::debug!("quic handshake error: {err}");
+            return;
+        }
+    };
+
+    let _open = state.track(|state| &state.open);
+    let peer_addr = conn.remote_address();
+    let tls_info = tls_info(&conn);
+
+    let mut conn = match h3::server::Connection::new(h3_quinn::Connection::new(conn)).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            tracing::error!("connection error: {err}");
+            return;
+        }
+    };
+
+    // Only the request timeout is applied by the watch, the idle one is tracked here.
+    let watch = Watch::new(
+        Timeouts {
+            idle: None,
+            ..timeouts
+        },
+        TokioTimer::new(),
+    );
+    let responder = Arc::new(
+        Responder::shared(state.clone(), Some(Arc::new(peer_addr)))
+            .peer_addr(Some(peer_addr))
+            .tls_info(Some(tls_info)),
+    );
+
+    let mut requests = JoinSet::new();
+    let mut closing = false;
+    let mut idle_deadline = timeouts.idle.map(|idle| Instant::now() + idle);
+
+    loop {
+        let idle = async {
+            match idle_deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => pending().await,
+            }
+        };
+
+        let resolver = tokio::select! {
+            resolver = conn.accept() => resolver,
+            Some(_) = requests.join_next() => {
+                if requests.is_empty() {
+                    idle_deadline = timeouts.idle.map(|idle| Instant::now() + idle);
+                }
+                continue;
+            }
+            () = idle, if requests.is_empty() && !closing => {
+                tracing::trace!("connection idle: {:?}", peer_addr);
+                // No requests are in flight, the connection is closed once dropped.
+                if let Err(err) = conn.shutdown(0).await {
+                    tracing::error!("connection error: {err}");
+                }
+                break;
+            }
+            () = state.shutdown.cancelled(), if !closing => {
+                closing = true;
+                // Waits for the accepted requests, the next `accept` returns `None`.
+                if let Err(err) = conn.shutdown(0).await {
+                    tracing::error!("connection error: {err}");
+                    break;
+                }
+                continue;
+            }
+        };
+
+        match resolver {
+            Ok(Some(resolver)) => {
+                let responder = responder.clone();
+                let watch = watch.clone();
+                requests.spawn(async move {
+                    let (req, stream) = match resolver.resolve_request().await {
+                        Ok(request) => request,
+                        Err(err) => {
+                            tracing::debug!("request error: {err}");
+                            return;
+                        }
+                    };
+                    if let Err(err) = respond(&responder, watch, req, stream).await {
+                        tracing::debug!("request error: {err}");
+                    }
+                });
+            }
+            Ok(None) => break,
+            Err(err) => {
+                if !err.is_h3_no_error() {
+                    tracing::error!("connection error: {err}");
+                }
+                break;
+            }
+        }
+    }
+
+    while requests.join_next().await.is_some() {}
+
+    tracing::trace!("connection dropped: {:?}", peer_addr);
+}
+
+/// Calls the handler with the request within the request timeout and sends the response.
+async fn respond(
+    responder: &Responder<Arc<SocketAddr>>,
+    watch: Arc<Watch>,
+    req: Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+) -> Result<(), StreamError> {
+    let (mut send, recv) = stream.split();
+    let head = req.method() == Method::HEAD;
+    let req = req.map(|()| {
+        Body::wrap(H3Body {
+            recv,
+            state: Some(false),
+        })
+    });
+
+    let (mut parts, body) = watch.request(responder.respond(req)).await.into_parts();
+    // The connection-specific headers are malformed in HTTP/3.
+    parts.headers.remove(CONNECTION);
+    parts.headers.remove(TRANSFER_ENCODING);
+    send.send_response(Response::from_parts(parts, ())).await?;
+
+    if !head {
+        let mut body = pin!(body);
+        while let Some(frame) = std::future::poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(err) => {
+                    tracing::error!("response body error: {err}");
+                    send.stop_stream(Code::H3_INTERNAL_ERROR);
+                    return Ok(());
+                }
+            };
+            match frame.into_data() {
+                Ok(data) => send.send_data(data).await?,
+                Err(frame) => {
+                    if let Ok(trailers) = frame.into_trailers() {
+                        send.send_trailers(trailers).await?;
+                    }
+                }
+            }
+        }
+    }
+
+    send.finish().await
+}
+
+/// The body of an HTTP/3 request, which is wrapped into [`Body`].
+struct H3Body {
+    recv: RequestStream<h3_quinn::RecvStream, Bytes>,
+    /// Whether the data is finished, `None` if the trailers are received too.
+    state: Option<bool>,
+}
+
+impl HttpBody for H3Body {
+    type Data = Bytes;
+    type Error = StreamError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.state {
+            None => return Poll::Ready(None),
+            Some(false) => match ready!(self.recv.poll_recv_data(cx)) {
+                Ok(Some(mut data)) => {
+                    let data = data.copy_to_bytes(data.remaining());
+                    return Poll::Ready(Some(Ok(Frame::data(data))));
+                }
+                Ok(None) => self.state = Some(true),
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            },
+            Some(true) => {}
+        }
+
+        let trailers = ready!(self.recv.poll_recv_trailers(cx));
+        self.state = None;
+        Poll::Ready(
+            trailers
+                .transpose()
+                .map(|trailers| trailers.map(Frame::trailers)),
+        )
+    }
+}
+
+impl fmt::Debug for H3Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H3Body").finish_non_exhaustive()
+    }
+}
+
+fn tls_info(conn: &quinn::Connection) -> TlsInfo {
+    let handshake = conn
+        .handshake_data()
+        .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok());
+    TlsInfo {
+        server_name: handshake.as_ref().and_then(|data| data.server_name.clone()),
+        alpn_protocol: handshake.and_then(|data| data.protocol),
+        peer_certificates: conn
+            .peer_identity()
+            .and_then(|certs| certs.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .map(|certs| {
+                certs
+                    .iter()
+                    .map(|cert| Bytes::copy_from_slice(cert))
+                    .collect()
+            }),
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Limits the concurrent connections of each peer IP.
+#[derive(Debug)]
+pub(crate) struct PeerLimit {
+    max: usize,
+    peers: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl PeerLimit {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Acquires a slot for the peer, returns `None` if the peer reaches the limit.
+    pub(crate) fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<PeerPermit> {
+        let mut peers = self.peers.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = peers.entry(ip).or_default();
+        if *count >= self.max {
+            return None;
+        }
+        *count += 1;
+        Some(PeerPermit {
+            limit: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// Releases the slot of the peer on drop.
+#[derive(Debug)]
+pub(crate) struct PeerPermit {
+    limit: Arc<PeerLimit>,
+    ip: IpAddr,
+}
+
+impl Drop for PeerPermit {
+    fn drop(&mut self) {
+        let mut peers = self
+            .limit
+            .peers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = peers.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                peers.remove(&self.ip);
+            }
+        }
+    }
+}
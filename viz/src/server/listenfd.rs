@@ -0,0 +1,232 @@
+use std::{env, fmt, io::Result};
+
+#[cfg(all(unix, feature = "upgrade"))]
+use std::{fs::File, io::Write, os::unix::fs::FileTypeExt};
+
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+
+#[cfg(unix)]
+use socket2::{SockRef, Type};
+use tokio::net::TcpListener;
+
+#[cfg(all(unix, feature = "unix-socket"))]
+use tokio::net::UnixListener;
+
+/// The listeners inherited from the parent process, e.g. by the [socket activation] of
+/// systemd, so the sockets are bound before the server starts and kept open across restarts.
+///
+/// The listeners are passed as the file descriptors from `3` to `3 + LISTEN_FDS - 1`, which
+/// are only taken if `LISTEN_PID` is missing or the PID of this process. They can be named by
+/// `FileDescriptorName=` of the socket units.
+///
+/// [`ListenFds::from_env`] modifies the environment, so it must be called before any threads are
+/// spawned, e.g. before the Tokio runtime is built.
+///
+/// ```no_run
+/// use tokio::net::TcpListener;
+/// use viz::{ListenFds, Router, serve};
+///
+/// fn main() -> std::io::Result<()> {
+///     let mut fds = ListenFds::from_env();
+///
+///     tokio::runtime::Runtime::new()?.block_on(async {
+///         let listener = match fds.take_tcp(0)? {
+///             Some(listener) => listener,
+///             None => TcpListener::bind("127.0.0.1:3000").await?,
+///         };
+///         serve(listener, Router::new()).await?;
+///         Ok(())
+///     })
+/// }
+/// ```
+///
+/// [socket activation]: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+pub struct ListenFds {
+    inner: listenfd::ListenFd,
+    names: Vec<String>,
+    /// The pipe for notifying the readiness, passed by an [`Upgrade`](super::Upgrade).
+    #[cfg(all(unix, feature = "upgrade"))]
+    ready: Option<File>,
+}
+
+impl ListenFds {
+    /// Takes the listeners from the environment, `LISTEN_PID` and `LISTEN_FDS` are removed,
+    /// so the child processes do not take them again.
+    ///
+    /// It must be called before any threads are spawned, since the environment is not safe to
+    /// modify while other threads may read it, e.g. resolving a host or spawning a process.
+    ///
+    /// `LISTEN_FDNAMES` is kept, because removing a variable needs `unsafe` code which Viz
+    /// forbids, but it has no effect without `LISTEN_FDS`. Remove it from the child processes
+    /// by [`Command::env_remove`](std::process::Command::env_remove) if they are confused by it.
+    #[must_use]
+    pub fn from_env() -> Self {
+        let names = env::var("LISTEN_FDNAMES")
+            .map(|names| names.split(':').map(ToOwned::to_owned).collect())
+            .unwrap_or_default();
+
+        // Only passed with the listeners, the variable inherited without them is stale.
+        #[cfg(all(unix, feature = "upgrade"))]
+        let ready = env::var_os("LISTEN_FDS")
+            .is_some()
+            .then(ready_from_env)
+            .flatten();
+
+        Self {
+            inner: listenfd::ListenFd::from_env(),
+            names,
+            #[cfg(all(unix, feature = "upgrade"))]
+            ready,
+        }
+    }
+
+    /// Returns the number of the inherited listeners, including the taken ones.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if there is no inherited listener.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the index of the listener which is named by `LISTEN_FDNAMES`.
+    #[must_use]
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.names.iter().take(self.len()).position(|n| n == name)
+    }
+
+    /// Takes the TCP listener at the index.
+    ///
+    /// Returns `None` if the index is out of range or has been taken, an error if the file
+    /// descriptor is not a TCP socket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    pub fn take_tcp(&mut self, idx: usize) -> Result<Option<TcpListener>> {
+        self.inner
+            .take_tcp_listener(idx)?
+            .map(tcp_from_std)
+            .transpose()
+    }
+
+    /// Takes the Unix listener at the index.
+    ///
+    /// Returns `None` if the index is out of range or has been taken, an error if the file
+    /// descriptor is not a Unix stream socket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    #[cfg(all(unix, feature = "unix-socket"))]
+    pub fn take_unix(&mut self, idx: usize) -> Result<Option<UnixListener>> {
+        self.inner
+            .take_unix_listener(idx)?
+            .map(unix_from_std)
+            .transpose()
+    }
+
+    /// Creates a TCP listener from a bound and listening socket, e.g. passed by a supervisor
+    /// other than systemd.
+    ///
+    /// Returns an error if the socket is not an IP stream socket, e.g. a UDP socket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    #[cfg(unix)]
+    pub fn tcp_from_fd(fd: OwnedFd) -> Result<TcpListener> {
+        stream_socket(&fd, "tcp")?;
+        let listener = std::net::TcpListener::from(fd);
+        // Fails if the socket is not an IP socket.
+        listener.local_addr()?;
+        tcp_from_std(listener)
+    }
+
+    /// Creates a Unix listener from a bound and listening socket, e.g. passed by a supervisor
+    /// other than systemd.
+    ///
+    /// Returns an error if the socket is not a Unix stream socket, e.g. a datagram socket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    #[cfg(all(unix, feature = "unix-socket"))]
+    pub fn unix_from_fd(fd: OwnedFd) -> Result<UnixListener> {
+        stream_socket(&fd, "unix")?;
+        let listener = std::os::unix::net::UnixListener::from(fd);
+        // Fails if the socket is not a Unix socket.
+        listener.local_addr()?;
+        unix_from_std(listener)
+    }
+
+    /// Notifies the old process that this one is ready, if it is spawned by an
+    /// [`Upgrade`](super::Upgrade), then the old process stops accepting.
+    ///
+    /// Returns `false` if it is not spawned by an [`Upgrade`](super::Upgrade), e.g. by systemd.
+    #[cfg(all(unix, feature = "upgrade"))]
+    pub fn notify_ready(&mut self) -> Result<bool> {
+        let Some(mut ready) = self.ready.take() else {
+            return Ok(false);
+        };
+        ready.write_all(&[1])?;
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for ListenFds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListenFds")
+            .field("len", &self.len())
+            .field("names", &self.names)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Takes the pipe named by [`Upgrade::READY_FD`](super::Upgrade::READY_FD).
+///
+/// The pipe is reopened with `O_CLOEXEC`, so the child processes do not inherit the reopened
+/// descriptor. The inherited descriptor is not owned by the process and is left open.
+#[cfg(all(unix, feature = "upgrade"))]
+fn ready_from_env() -> Option<File> {
+    let fd = env::var(super::Upgrade::READY_FD)
+        .ok()?
+        .parse::<i32>()
+        .ok()?;
+    let ready = File::options()
+        .write(true)
+        .open(format!("/dev/fd/{fd}"))
+        .ok()?;
+    if !ready.metadata().ok()?.file_type().is_fifo() {
+        return None;
+    }
+    Some(ready)
+}
+
+/// Checks the `SO_TYPE` of the socket, the address family is checked by its local address.
+#[cfg(unix)]
+fn stream_socket(fd: &OwnedFd, hint: &str) -> Result<()> {
+    if SockRef::from(fd).r#type()? == Type::STREAM {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("the socket is not a {hint} stream socket"),
+        ))
+    }
+}
+
+fn tcp_from_std(listener: std::net::TcpListener) -> Result<TcpListener> {
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+#[cfg(all(unix, feature = "unix-socket"))]
+fn unix_from_std(listener: std::os::unix::net::UnixListener) -> Result<UnixListener> {
+    listener.set_nonblocking(true)?;
+    UnixListener::from_std(listener)
+}
@@ -0,0 +1,246 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, BufReader},
+    sync::mpsc::Sender,
+};
+
+use super::handshake::Limits;
+use crate::types::TlsInfo;
+
+/// The signature of the version 2 header.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of the version 1 header, including the CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Accepts the connections from a load balancer which sends the [PROXY protocol] header,
+/// version 1 or 2, before the data, then the address of the client in the header is seen by
+/// the handlers as [`remote_addr`](crate::RequestExt::remote_addr).
+///
+/// The header is required, the connections without a valid header are closed. The address of
+/// the balancer is kept if the header is sent for the health checks, i.e. `UNKNOWN` of version
+/// 1 or `LOCAL` of version 2.
+///
+/// Only the balancers should be able to connect, since the clients could send a forged header.
+///
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use tokio::net::TcpListener;
+/// use viz::{ProxyProtocol, Router, serve};
+///
+/// let listener = ProxyProtocol::new(TcpListener::bind("127.0.0.1:3000").await?);
+/// serve(listener, Router::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [PROXY protocol]: https://www.haproxy.org/download/3.0/doc/proxy-protocol.txt
+#[derive(Debug)]
+pub struct ProxyProtocol<L> {
+    inner: L,
+    limits: Limits,
+}
+
+impl<L> ProxyProtocol<L> {
+    /// Wraps the listener, the header must be received in 1 second by default.
+    pub const fn new(inner: L) -> Self {
+        Self {
+            inner,
+            limits: Limits::new(Duration::from_secs(1)),
+        }
+    }
+
+    /// Specifies how long to wait for the header, the balancers send it immediately.
+    ///
+    /// The headers are read concurrently when served, a slow header does not delay the next
+    /// connections.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = timeout;
+        self
+    }
+
+    /// Limits the number of the connections whose headers are pending, defaults to 1024,
+    /// accepting new connections is paused until a pending one finishes.
+    #[must_use]
+    pub const fn max_pending(mut self, max: usize) -> Self {
+        self.limits.max = if max == 0 { 1 } else { max };
+        self
+    }
+
+    /// Gets the listener.
+    pub const fn get_ref(&self) -> &L {
+        &self.inner
+    }
+}
+
+impl<L> super::Listener for ProxyProtocol<L>
+where
+    L: super::Listener<Addr = SocketAddr> + Send + Sync,
+    L::Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = BufReader<L::Io>;
+    type Addr = SocketAddr;
+
+    /// Reads the headers concurrently, a stalled client does not delay the others.
+    async fn incoming(self, tx: Sender<Result<(Self::Io, Self::Addr)>>) {
+        super::handshake::incoming(self.inner, tx, self.limits, move |io, addr| async move {
+            let mut io = BufReader::with_capacity(512, io);
+            match read_header(&mut io).await {
+                Ok(source) => Ok((io, source.unwrap_or(addr))),
+                Err(e) => Err(Error::new(
+                    e.kind(),
+                    format!("invalid proxy protocol header from {addr}: {e}"),
+                )),
+            }
+        })
+        .await;
+    }
+
+    async fn accept(&self) -> Result<(Self::Io, Self::Addr)> {
+        loop {
+            let (io, addr) = self.inner.accept().await?;
+            // The header is short, the larger reads of the data bypass the buffer.
+            let mut io = BufReader::with_capacity(512, io);
+
+            match tokio::time::timeout(self.limits.timeout, read_header(&mut io)).await {
+                Ok(Ok(source)) => return Ok((io, source.unwrap_or(addr))),
+                Ok(Err(e)) => tracing::debug!("invalid proxy protocol header from {addr}: {e}"),
+                Err(_) => tracing::debug!("proxy protocol header timed out from {addr}"),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        Some(*addr)
+    }
+
+    fn tls_info(io: &Self::Io) -> Option<TlsInfo> {
+        L::tls_info(io.get_ref())
+    }
+}
+
+/// Reads the header, returns the source address if it is proxied.
+async fn read_header<I>(io: &mut BufReader<I>) -> Result<Option<SocketAddr>>
+where
+    I: AsyncRead + Unpin,
+{
+    let first = io.fill_buf().await?.first().copied();
+    match first {
+        Some(b'P') => read_v1(io).await,
+        Some(b'\r') => read_v2(io).await,
+        Some(_) => Err(invalid("missing header")),
+        None => Err(ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+/// Reads the human-readable header, e.g. `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`.
+async fn read_v1<I>(io: &mut BufReader<I>) -> Result<Option<SocketAddr>>
+where
+    I: AsyncRead + Unpin,
+{
+    let mut line = Vec::with_capacity(V1_MAX_LEN);
+    (&mut *io)
+        .take(V1_MAX_LEN as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    let line = line
+        .strip_suffix(b"\r\n")
+        .and_then(|line| std::str::from_utf8(line).ok())
+        .ok_or_else(|| invalid("malformed version 1 header"))?;
+
+    let mut parts = line.split(' ');
+    if parts.next() != Some("PROXY") {
+        return Err(invalid("malformed version 1 header"));
+    }
+
+    match parts.next() {
+        Some("UNKNOWN") => Ok(None),
+        Some(protocol @ ("TCP4" | "TCP6")) => {
+            let (Some(ip), Some(_), Some(port), Some(_), None) = (
+                parts.next(),
+                parts.next(),
+                parts.next(),
+                parts.next(),
+                parts.next(),
+            ) else {
+                return Err(invalid("malformed version 1 header"));
+            };
+
+            let ip = if protocol == "TCP4" {
+                ip.parse::<Ipv4Addr>().map(Into::into)
+            } else {
+                ip.parse::<Ipv6Addr>().map(Into::into)
+            }
+            .map_err(|_| invalid("invalid source address"))?;
+            let port = port.parse().map_err(|_| invalid("invalid source port"))?;
+
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("unsupported protocol")),
+    }
+}
+
+/// Reads the binary header.
+async fn read_v2<I>(io: &mut BufReader<I>) -> Result<Option<SocketAddr>>
+where
+    I: AsyncRead + Unpin,
+{
+    let mut header = [0; 16];
+    io.read_exact(&mut header).await?;
+    if !header.starts_with(V2_SIGNATURE) {
+        return Err(invalid("malformed version 2 header"));
+    }
+
+    let version_command = header[12];
+    let family_protocol = header[13];
+    let mut addresses = vec![0; usize::from(u16::from_be_bytes([header[14], header[15]]))];
+    // The TLVs after the addresses are skipped.
+    io.read_exact(&mut addresses).await?;
+
+    match version_command {
+        // `LOCAL`
+        0x20 => return Ok(None),
+        // `PROXY`
+        0x21 => {}
+        _ => return Err(invalid("unsupported version or command")),
+    }
+
+    match family_protocol {
+        // `TCP` over IPv4
+        0x11 => {
+            let address = addresses
+                .first_chunk::<12>()
+                .ok_or_else(|| invalid("truncated addresses"))?;
+            let ip = Ipv4Addr::from(*address.first_chunk::<4>().expect("4 bytes"));
+            let port = u16::from_be_bytes([address[8], address[9]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // `TCP` over IPv6
+        0x21 => {
+            let address = addresses
+                .first_chunk::<36>()
+                .ok_or_else(|| invalid("truncated addresses"))?;
+            let ip = Ipv6Addr::from(*address.first_chunk::<16>().expect("16 bytes"));
+            let port = u16::from_be_bytes([address[32], address[33]]);
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // `UNSPEC`, the address of the balancer is kept.
+        0x00 => Ok(None),
+        _ => Err(invalid("unsupported address family or protocol")),
+    }
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
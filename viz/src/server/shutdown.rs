@@ -0,0 +1,121 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::{sync::Notify, task::AbortHandle};
+
+/// The summary of a graceful shutdown.
+#[derive(Debug)]
+pub struct ShutdownSummary<A> {
+    /// The number of the connections which were drained before the timeout.
+    pub drained: usize,
+    /// The peer addresses of the connections which were force-closed after the timeout.
+    pub forced: Vec<Arc<A>>,
+}
+
+/// A callback fired on shutdown.
+pub(crate) struct Hook(Box<dyn FnOnce() + Send>);
+
+impl Hook {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Self(Box::new(f))
+    }
+
+    pub(crate) fn call(self) {
+        (self.0)();
+    }
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+/// The open connections, which can be aborted if they are not drained in time.
+#[derive(Debug)]
+pub(crate) struct Connections<A> {
+    next: AtomicU64,
+    open: Mutex<HashMap<u64, (Arc<A>, AbortHandle)>>,
+    /// Notified once the last open connection is finished.
+    empty: Notify,
+}
+
+impl<A> Connections<A>
+where
+    A: Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            open: Mutex::new(HashMap::new()),
+            empty: Notify::new(),
+        }
+    }
+
+    /// Spawns a task for serving the connection, which is removed once finished.
+    pub(crate) fn spawn<F>(self: &Arc<Self>, peer_addr: Arc<A>, conn: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        // Holds the lock until the task is registered, the task can not remove itself before.
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let conns = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            conn.await;
+            if let Some(conns) = conns.upgrade() {
+                let mut open = conns.open.lock().unwrap_or_else(PoisonError::into_inner);
+                open.remove(&id);
+                if open.is_empty() {
+                    conns.empty.notify_waiters();
+                }
+            }
+        });
+
+        open.insert(id, (peer_addr, task.abort_handle()));
+    }
+
+    /// Returns the number of the open connections.
+    pub(crate) fn len(&self) -> usize {
+        self.open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Waits until the open connections are finished.
+    #[cfg(feature = "http3")]
+    pub(crate) async fn drained(&self) {
+        loop {
+            let notified = self.empty.notified();
+            if self.len() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Aborts the open connections, returns their peer addresses.
+    pub(crate) fn abort_all(&self) -> Vec<Arc<A>> {
+        self.open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .drain()
+            .map(|(_, (peer_addr, task))| {
+                task.abort();
+                peer_addr
+            })
+            .collect()
+    }
+}
@@ -13,4 +13,8 @@ impl super::Listener for TcpListener {
     fn local_addr(&self) -> Result<Self::Addr> {
         Self::local_addr(self)
     }
+
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        Some(*addr)
+    }
 }
@@ -1,5 +1,9 @@
 //! A TLS listener wrapper.
 
+use std::time::Duration;
+
+use super::handshake::Limits;
+
 /// `native_tls`
 #[cfg(feature = "native-tls")]
 pub mod native_tls;
@@ -8,11 +12,15 @@ pub mod native_tls;
 #[cfg(feature = "rustls")]
 pub mod rustls;
 
+mod redirect;
+pub use redirect::HttpsRedirect;
+
 /// Unified TLS listener type.
 #[derive(Debug)]
 pub struct TlsListener<T, A> {
     pub(crate) inner: T,
     pub(crate) acceptor: A,
+    pub(crate) limits: Limits,
 }
 
 impl<T, A> TlsListener<T, A> {
@@ -21,9 +29,26 @@ impl<T, A> TlsListener<T, A> {
         Self {
             inner: t,
             acceptor: a,
+            limits: Limits::new(Duration::from_secs(10)),
         }
     }
 
+    /// Specifies how long a handshake can take, defaults to 10 seconds, the stalled clients are
+    /// closed.
+    #[must_use]
+    pub const fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = timeout;
+        self
+    }
+
+    /// Limits the number of the pending handshakes, defaults to 1024, accepting new
+    /// connections is paused until a pending one finishes.
+    #[must_use]
+    pub const fn max_handshakes(mut self, max: usize) -> Self {
+        self.limits.max = if max == 0 { 1 } else { max };
+        self
+    }
+
     /// Gets the listener.
     pub const fn get_ref(&self) -> &T {
         &self.inner
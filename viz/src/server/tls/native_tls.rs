@@ -1,9 +1,12 @@
 use std::{fmt, io::Result as IoResult, net::SocketAddr};
 
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc::Sender,
+};
 use tokio_native_tls::{TlsStream, native_tls::TlsAcceptor as TlsAcceptorWrapper};
 
-use crate::{Error, Result};
+use crate::{Error, Result, types::TlsInfo};
 
 pub use tokio_native_tls::{TlsAcceptor, native_tls::Identity};
 
@@ -37,21 +40,52 @@ impl Config {
     }
 }
 
-impl crate::Listener for crate::tls::TlsListener<TcpListener, TlsAcceptor> {
-    type Io = TlsStream<TcpStream>;
+impl<T> crate::Listener for crate::tls::TlsListener<T, TlsAcceptor>
+where
+    T: crate::Listener<Addr = SocketAddr> + Send + Sync,
+    T::Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = TlsStream<T::Io>;
     type Addr = SocketAddr;
 
+    /// Runs the handshakes concurrently, a stalled client does not delay the others.
+    async fn incoming(self, tx: Sender<IoResult<(Self::Io, Self::Addr)>>) {
+        let acceptor = self.acceptor;
+        crate::server::handshake::incoming(self.inner, tx, self.limits, move |stream, addr| {
+            let acceptor = acceptor.clone();
+            async move {
+                let stream = acceptor
+                    .accept(stream)
+                    .await
+                    .map_err(std::io::Error::other)?;
+                Ok((stream, addr))
+            }
+        })
+        .await;
+    }
+
     async fn accept(&self) -> IoResult<(Self::Io, Self::Addr)> {
         let (stream, addr) = self.inner.accept().await?;
-        let stream = self
-            .acceptor
-            .accept(stream)
-            .await
-            .map_err(std::io::Error::other)?;
+        let accept = async {
+            self.acceptor
+                .accept(stream)
+                .await
+                .map_err(std::io::Error::other)
+        };
+        let stream = self.limits.run(accept).await?;
         Ok((stream, addr))
     }
 
     fn local_addr(&self) -> IoResult<Self::Addr> {
         self.inner.local_addr()
     }
+
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        Some(*addr)
+    }
+
+    /// The SNI and ALPN are not exposed by `native-tls`.
+    fn tls_info(_: &Self::Io) -> Option<TlsInfo> {
+        Some(TlsInfo::default())
+    }
 }
@@ -0,0 +1,89 @@
+use hyper::http::uri::Authority;
+
+use crate::{
+    Handler, IntoResponse, Request, RequestExt, Response, ResponseExt, Result, Router, StatusCode,
+    header::HOST,
+};
+
+/// Redirects the plain HTTP requests to HTTPS with `308 Permanent Redirect`, which is served by
+/// a companion server of the [`TlsListener`](super::TlsListener).
+///
+/// ```no_run
+/// # async fn run(listener: tokio::net::TcpListener) {
+/// use viz::{serve, tls::HttpsRedirect};
+///
+/// let redirect = HttpsRedirect::new().map_port(8080, 8443);
+/// serve(listener, redirect.into()).await.ok();
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HttpsRedirect {
+    port: Option<u16>,
+    ports: Vec<(u16, u16)>,
+}
+
+impl HttpsRedirect {
+    /// Creates a redirect to the default HTTPS port 443.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the HTTPS port which the requests are redirected to.
+    #[must_use]
+    pub const fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Redirects the requests to the `http` port to the `https` port, e.g. `8080` to `8443`.
+    #[must_use]
+    pub fn map_port(mut self, http: u16, https: u16) -> Self {
+        self.ports.push((http, https));
+        self
+    }
+
+    /// Returns the HTTPS location of the request.
+    fn location(&self, req: &Request) -> Option<String> {
+        let authority = match req.uri().authority() {
+            Some(authority) => authority.clone(),
+            None => req.header::<_, Authority>(HOST)?,
+        };
+
+        let port = authority
+            .port_u16()
+            .and_then(|port| {
+                self.ports
+                    .iter()
+                    .find_map(|(http, https)| (*http == port).then_some(*https))
+            })
+            .or(self.port)
+            .filter(|port| *port != 443);
+
+        let mut location = format!("https://{}", authority.host());
+        if let Some(port) = port {
+            location.push(':');
+            location.push_str(&port.to_string());
+        }
+        location.push_str(req.uri().path_and_query().map_or("/", |pq| pq.as_str()));
+
+        Some(location)
+    }
+}
+
+#[crate::async_trait]
+impl Handler<Request> for HttpsRedirect {
+    type Output = Result<Response>;
+
+    async fn call(&self, req: Request) -> Self::Output {
+        self.location(&req)
+            .map(Response::permanent)
+            .ok_or_else(|| StatusCode::BAD_REQUEST.into_error())
+    }
+}
+
+impl From<HttpsRedirect> for Router {
+    fn from(redirect: HttpsRedirect) -> Self {
+        Self::new().fallback(redirect)
+    }
+}
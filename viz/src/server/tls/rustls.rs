@@ -1,164 +1,214 @@
 use std::{
-    io::{Error as IoError, ErrorKind, Result as IoResult},
+    fmt,
+    io::Result as IoResult,
     net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError, RwLock},
 };
 
-use tokio::net::{TcpListener, TcpStream};
-use tokio_rustls::{
-    rustls::{RootCertStore, ServerConfig, pki_types::PrivateKeyDer, server::WebPkiClientVerifier},
-    server::TlsStream,
+use futures_util::{Stream, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc::Sender,
 };
+use tokio_rustls::server::TlsStream;
 
-use crate::{Error, Result};
+use crate::{Result, types::TlsInfo};
 
 pub use tokio_rustls::TlsAcceptor;
+pub use viz_core::tls::rustls::Config;
 
-/// Tls client authentication configuration.
-#[derive(Debug)]
-pub(crate) enum ClientAuth {
-    /// No client auth.
-    Off,
-    /// Allow any anonymous or authenticated client.
-    Optional(Vec<u8>),
-    /// Allow any authenticated client.
-    Required(Vec<u8>),
+fn tls_info<IO>(stream: &TlsStream<IO>) -> TlsInfo {
+    viz_core::tls::rustls::tls_info(stream.get_ref().1)
 }
 
-/// `rustls`'s config.
-#[derive(Debug)]
-pub struct Config {
-    cert: Vec<u8>,
-    key: Vec<u8>,
-    ocsp_resp: Vec<u8>,
-    client_auth: ClientAuth,
-}
+impl<T> crate::Listener for crate::tls::TlsListener<T, TlsAcceptor>
+where
+    T: crate::Listener<Addr = SocketAddr> + Send + Sync,
+    T::Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = TlsStream<T::Io>;
+    type Addr = SocketAddr;
 
-impl Default for Config {
-    fn default() -> Self {
-        Self::new()
+    /// Runs the handshakes concurrently, a stalled client does not delay the others.
+    async fn incoming(self, tx: Sender<IoResult<(Self::Io, Self::Addr)>>) {
+        let acceptor = self.acceptor;
+        crate::server::handshake::incoming(self.inner, tx, self.limits, move |stream, addr| {
+            let accept = acceptor.accept(stream);
+            async move { Ok((accept.await?, addr)) }
+        })
+        .await;
     }
-}
 
-impl Config {
-    /// Create a new Tls config
-    #[must_use]
-    pub fn new() -> Self {
-        Self {
-            cert: Vec::new(),
-            key: Vec::new(),
-            client_auth: ClientAuth::Off,
-            ocsp_resp: Vec::new(),
-        }
+    async fn accept(&self) -> IoResult<(Self::Io, Self::Addr)> {
+        let (stream, addr) = self.inner.accept().await?;
+        let stream = self.limits.run(self.acceptor.accept(stream)).await?;
+        Ok((stream, addr))
     }
 
-    /// sets the Tls certificate
-    #[must_use]
-    pub fn cert(mut self, cert: impl Into<Vec<u8>>) -> Self {
-        self.cert = cert.into();
-        self
+    fn local_addr(&self) -> IoResult<Self::Addr> {
+        self.inner.local_addr()
     }
 
-    /// sets the Tls key
-    #[must_use]
-    pub fn key(mut self, key: impl Into<Vec<u8>>) -> Self {
-        self.key = key.into();
-        self
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        Some(*addr)
     }
 
-    /// Sets the trust anchor for optional Tls client authentication
-    #[must_use]
-    pub fn client_auth_optional(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
-        self.client_auth = ClientAuth::Optional(trust_anchor.into());
-        self
+    fn tls_info(io: &Self::Io) -> Option<TlsInfo> {
+        Some(tls_info(io))
     }
+}
 
-    /// Sets the trust anchor for required Tls client authentication
-    #[must_use]
-    pub fn client_auth_required(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
-        self.client_auth = ClientAuth::Required(trust_anchor.into());
-        self
+/// A TLS acceptor whose certificates can be reloaded at runtime, without restarting the server.
+///
+/// The new connections are accepted with the reloaded config, while the established ones are
+/// kept.
+///
+/// ```no_run
+/// # async fn run(listener: tokio::net::TcpListener) -> viz::Result<()> {
+/// use viz::{Router, serve, tls};
+///
+/// let config = tls::rustls::Config::new()
+///     .cert(std::fs::read("cert.pem")?)
+///     .key(std::fs::read("key.pem")?);
+/// let acceptor = tls::rustls::ReloadableAcceptor::new(config)?;
+///
+/// // Reloads the certificate and the key from the files every day.
+/// tokio::spawn({
+///     let acceptor = acceptor.clone();
+///     async move {
+///         let mut interval = tokio::time::interval(std::time::Duration::from_secs(86400));
+///         loop {
+///             interval.tick().await;
+///             if let Err(e) = acceptor.reload_files("cert.pem", "key.pem") {
+///                 eprintln!("failed to reload certificates: {e}");
+///             }
+///         }
+///     }
+/// });
+///
+/// serve(tls::TlsListener::new(listener, acceptor), Router::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReloadableAcceptor {
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+    config: Arc<Mutex<Config>>,
+}
+
+impl ReloadableAcceptor {
+    /// Creates a new acceptor with the config.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the config is invalid.
+    pub fn new(config: Config) -> Result<Self> {
+        let acceptor = TlsAcceptor::from(Arc::new(config.clone().build()?));
+        Ok(Self {
+            acceptor: Arc::new(RwLock::new(acceptor)),
+            config: Arc::new(Mutex::new(config)),
+        })
     }
 
-    /// sets the DER-encoded OCSP response
+    /// Returns the current acceptor.
     #[must_use]
-    pub fn ocsp_resp(mut self, ocsp_resp: impl Into<Vec<u8>>) -> Self {
-        self.ocsp_resp = ocsp_resp.into();
-        self
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 
-    /// builds the Tls `ServerConfig`
+    /// Reloads the config, the current one is kept if the new one is invalid.
     ///
     /// # Errors
-    pub fn build(self) -> Result<ServerConfig> {
-        fn read_trust_anchor(mut trust_anchor: &[u8]) -> Result<RootCertStore> {
-            let certs = rustls_pemfile::certs(&mut trust_anchor)
-                .collect::<IoResult<Vec<_>>>()
-                .map_err(Error::boxed)?;
-            let mut store = RootCertStore::empty();
-            for cert in certs {
-                store.add(cert).map_err(Error::boxed)?;
-            }
-            Ok(store)
-        }
+    ///
+    /// Will return `Err` if the config is invalid.
+    pub fn reload(&self, config: Config) -> Result<()> {
+        let acceptor = TlsAcceptor::from(Arc::new(config.clone().build()?));
+        *self
+            .acceptor
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = acceptor;
+        *self.config.lock().unwrap_or_else(PoisonError::into_inner) = config;
+        Ok(())
+    }
 
-        let certs = rustls_pemfile::certs(&mut self.cert.as_slice())
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(Error::boxed)?;
-
-        let keys = {
-            let mut pkcs8 = rustls_pemfile::pkcs8_private_keys(&mut self.key.as_slice())
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(Error::boxed)?;
-            if pkcs8.is_empty() {
-                let mut rsa = rustls_pemfile::rsa_private_keys(&mut self.key.as_slice())
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(Error::boxed)?;
-
-                if rsa.is_empty() {
-                    return Err(Error::boxed(IoError::new(
-                        ErrorKind::InvalidData,
-                        "failed to parse tls private keys",
-                    )));
-                }
-                PrivateKeyDer::Pkcs1(rsa.remove(0))
-            } else {
-                PrivateKeyDer::Pkcs8(pkcs8.remove(0))
-            }
-        };
-
-        let client_auth = match self.client_auth {
-            ClientAuth::Off => WebPkiClientVerifier::no_client_auth(),
-            ClientAuth::Optional(trust_anchor) => {
-                WebPkiClientVerifier::builder(read_trust_anchor(&trust_anchor)?.into())
-                    .allow_unauthenticated()
-                    .build()
-                    .map_err(Error::boxed)?
-            }
-            ClientAuth::Required(trust_anchor) => {
-                WebPkiClientVerifier::builder(read_trust_anchor(&trust_anchor)?.into())
-                    .build()
-                    .map_err(Error::boxed)?
+    /// Reloads the certificate and the key from the files, the other settings are kept.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the files can not be read or the certificate and the key are
+    /// invalid.
+    pub fn reload_files(&self, cert: impl AsRef<Path>, key: impl AsRef<Path>) -> Result<()> {
+        let config = self
+            .config
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .cert(std::fs::read(cert)?)
+            .key(std::fs::read(key)?);
+        self.reload(config)
+    }
+
+    /// Reloads the configs yielded by the stream until it ends, the invalid ones are skipped.
+    pub async fn reload_stream<S>(self, configs: S)
+    where
+        S: Stream<Item = Config>,
+    {
+        let mut configs = std::pin::pin!(configs);
+        while let Some(config) = configs.next().await {
+            if let Err(e) = self.reload(config) {
+                tracing::error!("failed to reload tls config: {e}");
             }
-        };
+        }
+    }
+}
 
-        ServerConfig::builder()
-            .with_client_cert_verifier(client_auth)
-            .with_single_cert_with_ocsp(certs, keys, self.ocsp_resp)
-            .map_err(Error::boxed)
+impl fmt::Debug for ReloadableAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableAcceptor").finish_non_exhaustive()
     }
 }
 
-impl crate::Listener for crate::tls::TlsListener<TcpListener, TlsAcceptor> {
-    type Io = TlsStream<TcpStream>;
+impl<T> crate::Listener for crate::tls::TlsListener<T, ReloadableAcceptor>
+where
+    T: crate::Listener<Addr = SocketAddr> + Send + Sync,
+    T::Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = TlsStream<T::Io>;
     type Addr = SocketAddr;
 
+    /// Runs the handshakes concurrently with the current config, a stalled client does not
+    /// delay the others.
+    async fn incoming(self, tx: Sender<IoResult<(Self::Io, Self::Addr)>>) {
+        let acceptor = self.acceptor;
+        crate::server::handshake::incoming(self.inner, tx, self.limits, move |stream, addr| {
+            let accept = acceptor.acceptor().accept(stream);
+            async move { Ok((accept.await?, addr)) }
+        })
+        .await;
+    }
+
     async fn accept(&self) -> IoResult<(Self::Io, Self::Addr)> {
         let (stream, addr) = self.inner.accept().await?;
-        let stream = self.acceptor.accept(stream).await?;
+        let stream = self
+            .limits
+            .run(self.acceptor.acceptor().accept(stream))
+            .await?;
         Ok((stream, addr))
     }
 
     fn local_addr(&self) -> IoResult<Self::Addr> {
         self.inner.local_addr()
     }
+
+    fn peer_addr(addr: &Self::Addr) -> Option<SocketAddr> {
+        Some(*addr)
+    }
+
+    fn tls_info(io: &Self::Io) -> Option<TlsInfo> {
+        Some(tls_info(io))
+    }
 }
@@ -0,0 +1,216 @@
+use std::{
+    env,
+    ffi::OsString,
+    io::{Error, ErrorKind, Result},
+    os::fd::{AsFd, OwnedFd},
+    path::PathBuf,
+    process::{Child, Command},
+    time::Duration,
+};
+
+use command_fds::{CommandFdExt, FdMapping};
+use nix::{
+    fcntl::{FcntlArg, FdFlag, fcntl},
+    unistd::pipe,
+};
+use tokio::{io::AsyncReadExt, net::unix::pipe::Receiver};
+
+/// The first file descriptor of the listeners in the new process.
+const FIRST_FD: i32 = 3;
+
+/// Re-executes the binary with the listening sockets for a zero-downtime upgrade.
+///
+/// The new process takes the listeners by [`ListenFds::from_env`](crate::ListenFds::from_env)
+/// and calls [`ListenFds::notify_ready`](crate::ListenFds::notify_ready) once it is serving.
+/// Then the old process stops accepting and drains its connections, e.g. by resolving the
+/// [`Server::signal`](crate::Server::signal). The sockets are never closed, the connections
+/// arriving meanwhile are queued.
+///
+/// The readiness is notified over a pipe, whose file descriptor follows the listeners and is
+/// named by the [`READY_FD`](Self::READY_FD) environment variable.
+///
+/// ```no_run
+/// # use viz::ListenFds;
+/// # async fn run(mut fds: ListenFds, mut upgrades: tokio::sync::mpsc::Receiver<()>) -> viz::Result<()> {
+/// use tokio::net::TcpListener;
+/// use viz::{Router, Upgrade, serve};
+///
+/// // `fds` is taken by `ListenFds::from_env` before the runtime is built.
+/// let listener = match fds.position("http").map(|idx| fds.take_tcp(idx)) {
+///     Some(listener) => listener?.expect("the inherited listener"),
+///     None => TcpListener::bind("127.0.0.1:3000").await?,
+/// };
+///
+/// let upgrade = Upgrade::new()?.listener("http", &listener)?;
+/// let signal = async move {
+///     // Upgrades on demand, e.g. on `SIGHUP`, and keeps serving if the new process fails.
+///     while upgrades.recv().await.is_some() {
+///         match upgrade.spawn().await {
+///             Ok(child) => {
+///                 tracing::info!("upgraded to {}", child.id());
+///                 break;
+///             }
+///             Err(e) => tracing::error!("failed to upgrade: {e}"),
+///         }
+///     }
+/// };
+///
+/// fds.notify_ready()?;
+/// serve(listener, Router::new()).signal(signal).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Upgrade {
+    program: PathBuf,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    listeners: Vec<(String, OwnedFd)>,
+    ready_timeout: Duration,
+}
+
+impl Upgrade {
+    /// The environment variable naming the file descriptor of the pipe for notifying the
+    /// readiness.
+    pub const READY_FD: &'static str = "VIZ_UPGRADE_READY_FD";
+
+    /// Re-executes the current binary with the same arguments.
+    pub fn new() -> Result<Self> {
+        Ok(Self::program(env::current_exe()?).args(env::args_os().skip(1)))
+    }
+
+    /// Executes another binary, e.g. the one installed at a fixed path.
+    pub fn program(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            listeners: Vec::new(),
+            ready_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Adds an argument of the new process.
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds the arguments of the new process.
+    #[must_use]
+    pub fn args<I>(mut self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets an environment variable of the new process, the others are inherited.
+    #[must_use]
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Passes a listener with the name, which is found by
+    /// [`ListenFds::position`](crate::ListenFds::position) in the new process.
+    ///
+    /// The socket is duplicated, the listener is still owned by the server.
+    pub fn listener(mut self, name: impl Into<String>, listener: &impl AsFd) -> Result<Self> {
+        let name = name.into();
+        if name.contains(':') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the name of a listener must not contain `:`",
+            ));
+        }
+        self.listeners
+            .push((name, listener.as_fd().try_clone_to_owned()?));
+        Ok(self)
+    }
+
+    /// Specifies how long to wait for the new process to be ready, defaults to 30 seconds.
+    #[must_use]
+    pub const fn ready_timeout(mut self, timeout: Duration) -> Self {
+        self.ready_timeout = timeout;
+        self
+    }
+
+    /// Spawns the new process and waits for it to be ready.
+    ///
+    /// The new process is killed if it exits or times out before being ready, it can be spawned
+    /// again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it is not called from a Tokio runtime.
+    pub async fn spawn(&self) -> Result<Child> {
+        let (ready, notifier) = pipe()?;
+        // Only the mapped notifier is inherited by the new process.
+        for fd in [&ready, &notifier] {
+            fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        }
+
+        let mut names = Vec::with_capacity(self.listeners.len());
+        let mut mappings = Vec::with_capacity(self.listeners.len() + 1);
+        for (name, fd) in &self.listeners {
+            names.push(name.as_str());
+            mappings.push(fd.try_clone()?);
+        }
+        let listen_fds = mappings.len();
+        let ready_fd = FIRST_FD + i32::try_from(listen_fds).map_err(Error::other)?;
+        mappings.push(notifier);
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(k, v)| (k, v)))
+            .env("LISTEN_FDS", listen_fds.to_string())
+            .env("LISTEN_FDNAMES", names.join(":"))
+            .env(Self::READY_FD, ready_fd.to_string())
+            .env_remove("LISTEN_PID")
+            .env_remove("LISTEN_FDS_FIRST_FD")
+            .fd_mappings(
+                mappings
+                    .into_iter()
+                    .zip(FIRST_FD..)
+                    .map(|(parent_fd, child_fd)| FdMapping {
+                        parent_fd,
+                        child_fd,
+                    })
+                    .collect(),
+            )
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        let mut child = command.spawn()?;
+        // Closes the notifier of this process, the reading ends if the new process exits.
+        drop(command);
+
+        let mut ready = Receiver::from_owned_fd(ready)?;
+        let mut buf = [0; 1];
+        let err = match tokio::time::timeout(self.ready_timeout, ready.read(&mut buf)).await {
+            Ok(Ok(1)) => return Ok(child),
+            Ok(Ok(_)) => Error::new(
+                ErrorKind::UnexpectedEof,
+                "the new process exited before being ready",
+            ),
+            Ok(Err(e)) => e,
+            Err(_) => Error::new(
+                ErrorKind::TimedOut,
+                "timed out waiting for the new process to be ready",
+            ),
+        };
+
+        // The process may have exited, it is reaped without blocking the runtime.
+        let _ = tokio::task::spawn_blocking(move || {
+            let _ = child.kill();
+            child.wait()
+        })
+        .await;
+
+        Err(err)
+    }
+}